@@ -1,8 +1,20 @@
-use math::{Vec2i, Vec3f};
+use math::{Mat4x4, Vec2i, Vec3f, Vec4f};
 use random::Source;
-use renderer::{obj::ObjData, Img, ImgColor};
-use sdl2::{event::Event, keyboard::Keycode, pixels::Color, rect::Point};
-use std::{io::BufRead, time::Duration};
+use renderer::{
+    mesh::{NormalWeighting, Sphere},
+    mtl::{Material, NormalSpace},
+    obj::ObjData,
+    tiled, Attenuation, BlinnPhongShader, Camera, CullMode, DepthCompare, Filter, Framebuffer,
+    GouraudShader, Img, ImgColor, Light, NormalMap, PhongShader, Pipeline, Projection, Sampler,
+    ShadowMap, Texture, TexturedShader, Wrap,
+};
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    pixels::{Color, PixelFormatEnum},
+    rect::Point,
+};
+use std::{io::BufRead, path::Path, time::Duration};
 
 pub mod hsv;
 pub mod math;
@@ -14,22 +26,128 @@ pub(crate) type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
 
 struct Model {
     obj: ObjData,
-    texture: image::ImageBuffer<image::Rgba<f32>, Vec<f32>>,
+    bounds: Sphere,
+    texture: Texture,
+    /// Normal map for `texture`, if there is one.
+    normal_map: Option<(Texture, NormalSpace)>,
+    /// Specular intensity for `texture`, if there is one.
+    specular_map: Option<Texture>,
+    /// Emissive color for `texture`, if there is one.
+    glow_map: Option<Texture>,
+    /// Materials from the OBJ's `mtllib`s.
+    materials: Vec<LoadedMaterial>,
+}
+
+/// A material with its texture maps loaded.
+struct LoadedMaterial {
+    material: Material,
+    diffuse_map: Option<Texture>,
+    specular_map: Option<Texture>,
+    emissive_map: Option<Texture>,
+    normal_map: Option<Texture>,
+}
+
+struct Scene {
+    camera: Camera,
+    lights: Vec<Light>,
+    /// Whether the first light casts shadows.
+    shadows: bool,
+    normal_mapping: bool,
+    sampler: Sampler,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shading {
+    Flat,
+    Gouraud,
+    Phong,
+    BlinnPhong,
+}
+
+/// Loads the image at `path` and makes its mipmaps.
+fn load_texture(path: &Path) -> Result<Texture, String> {
+    let texture = if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("tga"))
+    {
+        renderer::tga::read(&mut std::io::BufReader::new(
+            std::fs::File::open(path).map_err(|e| e.to_string())?,
+        ))
+        .and_then(|tga| tga.to_texture())
+        .map_err(|e| e.to_string())?
+    } else {
+        image::open(path)
+            .map_err(|e| e.to_string())?
+            .into_rgba32f()
+            .into()
+    };
+
+    Ok(texture.with_mipmaps())
+}
+
+/// Loads `path` if it exists.
+fn load_optional_texture(path: &Path) -> Result<Option<Texture>, String> {
+    Some(path)
+        .filter(|p| p.exists())
+        .map(load_texture)
+        .transpose()
+}
+
+fn load_materials(obj: &ObjData, dir: &Path) -> Result<Vec<LoadedMaterial>, String> {
+    let mut materials = vec![];
+    let load = |map: &Option<String>| map.as_ref().map(|m| load_texture(&dir.join(m))).transpose();
+
+    for lib in &obj.material_libs {
+        let lib_materials = match renderer::mtl::load(dir.join(lib)) {
+            Ok(m) => m,
+            Err(e) => {
+                // faces using its materials fall back to the default one
+                eprintln!("Skipping material library {lib}: {e}");
+                continue;
+            }
+        };
+
+        for material in lib_materials {
+            materials.push(LoadedMaterial {
+                diffuse_map: load(&material.diffuse_map)?,
+                specular_map: load(&material.specular_map)?,
+                emissive_map: load(&material.emissive_map)?,
+                normal_map: load(&material.normal_map)?,
+                material,
+            });
+        }
+    }
+
+    Ok(materials)
 }
 
 fn main() -> Result<(), String> {
-    let head = Model {
-        obj: renderer::obj::read(
-            std::io::BufReader::new(
-                std::fs::File::open("african_head.obj").map_err(|e| e.to_string())?,
-            )
-            .lines(),
+    let mut obj = renderer::obj::read(
+        std::io::BufReader::new(
+            std::fs::File::open("african_head.obj").map_err(|e| e.to_string())?,
         )
-        .map_err(|e| e.to_string())?,
+        .lines(),
+    )
+    .map_err(|e| e.to_string())?;
 
-        texture: image::open("african_head_diffuse.tga")
-            .map_err(|e| e.to_string())?
-            .into_rgba32f(),
+    if obj.vertex_normals.is_empty() {
+        renderer::mesh::generate_normals(&mut obj, NormalWeighting::Angle, None);
+    }
+
+    let head = Model {
+        materials: load_materials(&obj, Path::new("."))?,
+        bounds: renderer::mesh::bounding_sphere(&obj.vertices),
+        obj,
+
+        texture: load_texture(Path::new("african_head_diffuse.tga"))?,
+        // the tangent-space map if there is one, the object-space one otherwise
+        normal_map: match load_optional_texture(Path::new("african_head_nm_tangent.tga"))? {
+            Some(map) => Some((map, NormalSpace::Tangent)),
+            None => load_optional_texture(Path::new("african_head_nm.tga"))?
+                .map(|map| (map, NormalSpace::Object)),
+        },
+        specular_map: load_optional_texture(Path::new("african_head_spec.tga"))?,
+        glow_map: load_optional_texture(Path::new("african_head_glow.tga"))?,
     };
 
     let sdl_context = sdl2::init()?;
@@ -44,14 +162,80 @@ fn main() -> Result<(), String> {
     let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
     let mut event_pump = sdl_context.event_pump()?;
 
-    let mut zbuffer = vec![0.; 1920 * 1080];
+    let camera = Camera::new(
+        Vec3f::new([0., 0., 3.]),
+        Vec3f::zero(),
+        Vec3f::new([0., 1., 0.]),
+        Projection::Perspective {
+            fov_y: std::f64::consts::FRAC_PI_4,
+            near: 0.1,
+            far: 100.,
+        },
+    );
+    let mut angle = 0.;
+    let mut shading = Shading::BlinnPhong;
+    let mut pipeline = Pipeline::new(CullMode::Clockwise);
+    pipeline.threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let lights = vec![
+        Light::directional(
+            Vec3f::new([-0.3, -0.2, -1.]),
+            Vec3f::new([1., 0.95, 0.9]),
+            0.8,
+        ),
+        Light::point(
+            Vec3f::new([1.5, 1., 1.5]),
+            Attenuation::new(1., 0.1, 0.05),
+            Vec3f::new([0.4, 0.6, 1.]),
+            1.,
+        ),
+    ];
+    let mut scene = Scene {
+        camera,
+        lights,
+        shadows: true,
+        normal_mapping: true,
+        sampler: Sampler::trilinear(Wrap::Repeat),
+    };
+
+    let texture_creator = canvas.texture_creator();
+    let background = MyColor(Color::RGB(75, 75, 75));
+    let mut fb = Framebuffer::new(0, 0, background.clone());
+    let mut screen = None;
+
     'running: loop {
-        canvas.set_draw_color(Color::RGB(75, 75, 75));
-        canvas.clear();
+        let (w, h) = canvas.output_size()?;
+        if (w as usize, h as usize) != (fb.width(), fb.height()) {
+            fb.resize(w as usize, h as usize, background.clone());
+            screen = Some(
+                texture_creator
+                    .create_texture_streaming(PixelFormatEnum::RGBA32, w, h)
+                    .map_err(|e| e.to_string())?,
+            );
+        }
+        fb.clear(background.clone());
+
+        let model = Mat4x4::rotation_y(angle);
+        pipeline.stats = Default::default();
+        render(&mut fb, &head, &scene, model, shading, &mut pipeline);
 
-        render(&mut canvas, &head, Some(&mut zbuffer))?;
+        if let Some(screen) = &mut screen {
+            screen
+                .update(None, &fb.to_rgba(), w as usize * 4)
+                .map_err(|e| e.to_string())?;
+            canvas.copy(screen, None, None)?;
+        }
         canvas.present();
 
+        let s = pipeline.stats;
+        canvas
+            .window_mut()
+            .set_title(&format!(
+                "3D test - {} submitted, {} culled, {} clipped, {} rasterized on {} threads",
+                s.submitted, s.culled, s.clipped, s.rasterized, pipeline.threads
+            ))
+            .map_err(|e| e.to_string())?;
+
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -60,6 +244,87 @@ fn main() -> Result<(), String> {
                     ..
                 } => break 'running,
 
+                Event::KeyDown {
+                    keycode: Some(Keycode::Left),
+                    ..
+                } => angle -= 0.1,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Right),
+                    ..
+                } => angle += 0.1,
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F),
+                    ..
+                } => shading = Shading::Flat,
+                Event::KeyDown {
+                    keycode: Some(Keycode::G),
+                    ..
+                } => shading = Shading::Gouraud,
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    ..
+                } => shading = Shading::Phong,
+                Event::KeyDown {
+                    keycode: Some(Keycode::B),
+                    ..
+                } => shading = Shading::BlinnPhong,
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
+                    ..
+                } => {
+                    pipeline.cull = match pipeline.cull {
+                        CullMode::Clockwise => CullMode::CounterClockwise,
+                        CullMode::CounterClockwise => CullMode::None,
+                        CullMode::None => CullMode::Clockwise,
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::S),
+                    ..
+                } => scene.shadows = !scene.shadows,
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    ..
+                } => scene.normal_mapping = !scene.normal_mapping,
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    ..
+                } => {
+                    // nearest, bilinear, trilinear
+                    let s = &mut scene.sampler;
+                    (s.filter, s.mipmap) = match (s.filter, s.mipmap) {
+                        (Filter::Nearest, _) => (Filter::Linear, None),
+                        (Filter::Linear, None) => (Filter::Linear, Some(Filter::Linear)),
+                        (Filter::Linear, Some(_)) => (Filter::Nearest, None),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Z),
+                    ..
+                } => {
+                    let camera = &mut scene.camera;
+                    camera.reversed_z = !camera.reversed_z;
+                    fb.depth_mut().set_compare(if camera.reversed_z {
+                        DepthCompare::Greater
+                    } else {
+                        DepthCompare::Less
+                    });
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::T),
+                    ..
+                } => {
+                    // cycle through 1, 2, 4, ... up to the number of cores
+                    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+                    pipeline.threads = if pipeline.threads >= cores {
+                        1
+                    } else {
+                        (pipeline.threads * 2).min(cores)
+                    };
+                }
+
                 _ => {}
             }
         }
@@ -94,30 +359,150 @@ impl Img for Canvas {
 }
 
 fn render(
-    canvas: &mut Canvas,
+    fb: &mut Framebuffer<MyColor>,
     model: &Model,
-    mut zbuffer: Option<&mut [f64]>,
-) -> Result<(), String> {
-    if let Some(zbuffer) = zbuffer.as_deref_mut() {
-        zbuffer.fill(f64::MIN);
+    scene: &Scene,
+    transform: Mat4x4,
+    shading: Shading,
+    pipeline: &mut Pipeline,
+) {
+    let aspect = fb.width() as f64 / fb.height() as f64;
+
+    let (camera, lights) = (&scene.camera, scene.lights.as_slice());
+    let view_projection = camera.view_projection(aspect);
+    let mvp = view_projection * transform;
+    let normal_matrix = renderer::shader::normal_matrix(transform);
+    let light_dir = Vec3f::new([0., 0., -1.]);
+
+    if !pipeline.mesh_visible(mvp, &model.bounds, model.obj.faces.len()) {
+        return;
     }
 
-    renderer::flat_shaded(
-        canvas,
-        canvas.size()?,
-        Vec3f::zero(),
-        model,
-        Vec3f::new([0., 0., -1.]),
-        MyColor(Color::MAGENTA),
-        zbuffer,
-    )?;
+    let lit = shading == Shading::BlinnPhong || !model.obj.materials.is_empty();
+    let shadow = (lit && scene.shadows)
+        .then(|| {
+            let c = model.bounds.center;
+            let bounds = Sphere {
+                center: (transform * Vec4f::new([c.x(), c.y(), c.z(), 1.])).div_w(),
+                ..model.bounds
+            };
+            ShadowMap::for_light(lights, 0, &bounds, 1024)
+        })
+        .flatten()
+        .map(|mut map| {
+            map.draw(&model.obj, transform);
+            map
+        });
 
-    // println!("draw");
+    if model.obj.materials.is_empty() {
+        let (color, texture) = (MyColor(Color::WHITE), Some(&model.texture));
 
-    Ok(())
+        match shading {
+            Shading::Flat => tiled::draw(
+                fb,
+                &model.obj,
+                &TexturedShader {
+                    mvp,
+                    normal_matrix,
+                    light_dir,
+                    texture: &model.texture,
+                    sampler: scene.sampler,
+                },
+                pipeline,
+            ),
+            Shading::Gouraud => tiled::draw(
+                fb,
+                &model.obj,
+                &GouraudShader {
+                    mvp,
+                    normal_matrix,
+                    light_dir,
+                    color,
+                    texture,
+                    sampler: scene.sampler,
+                },
+                pipeline,
+            ),
+            Shading::Phong => tiled::draw(
+                fb,
+                &model.obj,
+                &PhongShader {
+                    mvp,
+                    normal_matrix,
+                    light_dir,
+                    color,
+                    texture,
+                    sampler: scene.sampler,
+                },
+                pipeline,
+            ),
+            Shading::BlinnPhong => {
+                let material = Material {
+                    ambient: Vec3f::new([0.1, 0.1, 0.1]),
+                    specular: Vec3f::new([0.4, 0.4, 0.4]),
+                    // all of the glow map's color
+                    emissive: match model.glow_map {
+                        Some(_) => Vec3f::one(),
+                        None => Vec3f::zero(),
+                    },
+                    shininess: 32.,
+                    ..Material::new("default")
+                };
+                let shader = BlinnPhongShader {
+                    diffuse_map: Some(&model.texture),
+                    specular_map: model.specular_map.as_ref(),
+                    emissive_map: model.glow_map.as_ref(),
+                    normal_map: model
+                        .normal_map
+                        .as_ref()
+                        .filter(|_| scene.normal_mapping)
+                        .map(|(map, space)| NormalMap::new(map, *space)),
+                    shadow: shadow.as_ref(),
+                    sampler: scene.sampler,
+                    ..BlinnPhongShader::new(
+                        transform,
+                        view_projection,
+                        camera.eye,
+                        lights,
+                        &material,
+                    )
+                };
+                tiled::draw(fb, &model.obj, &shader, pipeline)
+            }
+        }
+        return;
+    }
+
+    // faces before the first `usemtl`, or with an unknown material, get the default one
+    let default = Material::new("default");
+    for (name, faces) in model.obj.material_ranges() {
+        let loaded = model
+            .materials
+            .iter()
+            .find(|m| Some(m.material.name.as_str()) == name);
+        let material = loaded.map_or(&default, |m| &m.material);
+
+        let shader = BlinnPhongShader {
+            diffuse_map: loaded.and_then(|m| m.diffuse_map.as_ref()),
+            specular_map: loaded.and_then(|m| m.specular_map.as_ref()),
+            emissive_map: loaded.and_then(|m| m.emissive_map.as_ref()),
+            normal_map: loaded
+                .and_then(|m| {
+                    Some(NormalMap::new(
+                        m.normal_map.as_ref()?,
+                        m.material.normal_space,
+                    ))
+                })
+                .filter(|_| scene.normal_mapping),
+            shadow: shadow.as_ref(),
+            sampler: scene.sampler,
+            ..BlinnPhongShader::new(transform, view_projection, camera.eye, lights, material)
+        };
+        tiled::draw_faces(fb, &model.obj, faces, &shader, pipeline);
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MyColor(Color);
 impl From<(u8, u8, u8)> for MyColor {
     fn from(value: (u8, u8, u8)) -> Self {
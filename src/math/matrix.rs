@@ -1,4 +1,4 @@
-use super::vec::Vecf;
+use super::vec::{Vec3f, Vec4f, Vecf};
 use std::array::from_fn as arr;
 
 pub type Matx<const N: usize> = Matrix<N, N>;
@@ -13,9 +13,9 @@ impl<const M: usize, const N: usize> Matrix<M, N> {
     pub const fn from_rows(rows: [Vecf<N>; M]) -> Self {
         Self(rows)
     }
-    // pub const fn from_columns(columns: [Vecf<M>; N]) -> Self {
-    //     Self(columns)
-    // }
+    pub fn from_columns(columns: [Vecf<M>; N]) -> Self {
+        Self(arr(|i| Vecf::new(arr(|j| columns[j][i]))))
+    }
 
     pub const fn zero() -> Self {
         Self([Vecf::zero(); M])
@@ -29,10 +29,137 @@ impl<const M: usize, const N: usize> Matrix<M, N> {
     }
 }
 
+/// Pivots smaller than this are treated as zero when eliminating.
+const SINGULAR_EPSILON: f64 = 1e-12;
+
 impl<const N: usize> Matrix<N, N> {
-    // pub const fn identity() -> Self {
-    //     Self(arr(asd))
-    // }
+    pub const fn identity() -> Self {
+        let mut m = Self::zero();
+
+        let mut i = 0;
+        while i < N {
+            m.0[i].0[i] = 1.;
+            i += 1;
+        }
+
+        m
+    }
+
+    /// Index of the row at or below `col` with the largest absolute value in column `col`.
+    fn pivot_row(&self, col: usize) -> usize {
+        (col..N)
+            .max_by(|&a, &b| self[a][col].abs().total_cmp(&self[b][col].abs()))
+            .unwrap_or(col)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let mut m = *self;
+        let mut det = 1.;
+
+        for col in 0..N {
+            let p = m.pivot_row(col);
+            if m[p][col].abs() < SINGULAR_EPSILON {
+                return 0.;
+            }
+            if p != col {
+                m.0.swap(p, col);
+                det = -det;
+            }
+
+            let pivot = m[col][col];
+            det *= pivot;
+
+            for row in col + 1..N {
+                let f = m[row][col] / pivot;
+                let r = m[col];
+                m[row] -= r * f;
+            }
+        }
+
+        det
+    }
+
+    /// Gauss-Jordan elimination, `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = *self;
+        let mut inv = Self::identity();
+
+        for col in 0..N {
+            let p = m.pivot_row(col);
+            if m[p][col].abs() < SINGULAR_EPSILON {
+                return None;
+            }
+            m.0.swap(p, col);
+            inv.0.swap(p, col);
+
+            let pivot = m[col][col];
+            m[col] /= pivot;
+            inv[col] /= pivot;
+
+            for row in 0..N {
+                if row == col {
+                    continue;
+                }
+
+                let f = m[row][col];
+                let (mr, ir) = (m[col], inv[col]);
+                m[row] -= mr * f;
+                inv[row] -= ir * f;
+            }
+        }
+
+        Some(inv)
+    }
+}
+
+impl Mat4x4 {
+    pub fn translation(t: Vec3f) -> Self {
+        Self([
+            Vec4f::new([1., 0., 0., t.x()]),
+            Vec4f::new([0., 1., 0., t.y()]),
+            Vec4f::new([0., 0., 1., t.z()]),
+            Vec4f::new([0., 0., 0., 1.]),
+        ])
+    }
+
+    pub fn scaling(s: Vec3f) -> Self {
+        Self([
+            Vec4f::new([s.x(), 0., 0., 0.]),
+            Vec4f::new([0., s.y(), 0., 0.]),
+            Vec4f::new([0., 0., s.z(), 0.]),
+            Vec4f::new([0., 0., 0., 1.]),
+        ])
+    }
+
+    pub fn rotation_x(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self([
+            Vec4f::new([1., 0., 0., 0.]),
+            Vec4f::new([0., c, -s, 0.]),
+            Vec4f::new([0., s, c, 0.]),
+            Vec4f::new([0., 0., 0., 1.]),
+        ])
+    }
+
+    pub fn rotation_y(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self([
+            Vec4f::new([c, 0., s, 0.]),
+            Vec4f::new([0., 1., 0., 0.]),
+            Vec4f::new([-s, 0., c, 0.]),
+            Vec4f::new([0., 0., 0., 1.]),
+        ])
+    }
+
+    pub fn rotation_z(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self([
+            Vec4f::new([c, -s, 0., 0.]),
+            Vec4f::new([s, c, 0., 0.]),
+            Vec4f::new([0., 0., 1., 0.]),
+            Vec4f::new([0., 0., 0., 1.]),
+        ])
+    }
 }
 
 impl<const M: usize, const N: usize> std::fmt::Display for Matrix<N, M> {
@@ -110,6 +237,24 @@ impl<const M: usize, const N: usize> std::ops::Mul<f64> for Matrix<M, N> {
     }
 }
 
+impl<const M: usize, const N: usize, const P: usize> std::ops::Mul<Matrix<N, P>> for Matrix<M, N> {
+    type Output = Matrix<M, P>;
+
+    fn mul(self, rhs: Matrix<N, P>) -> Self::Output {
+        Matrix(arr(|i| {
+            Vecf::new(arr(|j| (0..N).map(|k| self[i][k] * rhs[k][j]).sum()))
+        }))
+    }
+}
+
+impl<const M: usize, const N: usize> std::ops::Mul<Vecf<N>> for Matrix<M, N> {
+    type Output = Vecf<M>;
+
+    fn mul(self, rhs: Vecf<N>) -> Self::Output {
+        Vecf::new(arr(|i| self[i].dot(rhs)))
+    }
+}
+
 impl<const M: usize, const N: usize> std::ops::Div<f64> for Matrix<M, N> {
     type Output = Self;
 
@@ -117,3 +262,103 @@ impl<const M: usize, const N: usize> std::ops::Div<f64> for Matrix<M, N> {
         Self(arr(|i| self.0[i] / rhs))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Vec2f, Vec3f};
+
+    fn assert_close<const M: usize, const N: usize>(a: Matrix<M, N>, b: Matrix<M, N>) {
+        for i in 0..M {
+            for j in 0..N {
+                assert!((a[(i, j)] - b[(i, j)]).abs() < 1e-9, "{a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn identity() {
+        let i = Mat3x3::identity();
+        assert_eq!(i[0], Vec3f::new([1., 0., 0.]));
+        assert_eq!(i[1], Vec3f::new([0., 1., 0.]));
+        assert_eq!(i[2], Vec3f::new([0., 0., 1.]));
+    }
+
+    #[test]
+    fn from_columns_is_transposed_from_rows() {
+        let cols = [
+            Vec2f::new([1., 2.]),
+            Vec2f::new([3., 4.]),
+            Vec2f::new([5., 6.]),
+        ];
+        let m: Matrix<2, 3> = Matrix::from_columns(cols);
+
+        assert_eq!(m, Matrix::from_rows(cols).transpose());
+        assert_eq!(m[0], Vec3f::new([1., 3., 5.]));
+    }
+
+    #[test]
+    fn matrix_product() {
+        let a = Matrix::<2, 3>::from_rows([Vec3f::new([1., 2., 3.]), Vec3f::new([4., 5., 6.])]);
+        let b = Matrix::<3, 2>::from_rows([
+            Vec2f::new([7., 8.]),
+            Vec2f::new([9., 10.]),
+            Vec2f::new([11., 12.]),
+        ]);
+
+        let expected = Mat2x2::from_rows([Vec2f::new([58., 64.]), Vec2f::new([139., 154.])]);
+        assert_eq!(a * b, expected);
+        assert_eq!(Mat2x2::identity() * expected, expected);
+    }
+
+    #[test]
+    fn matrix_vector_product() {
+        let a = Matrix::<2, 3>::from_rows([Vec3f::new([1., 2., 3.]), Vec3f::new([4., 5., 6.])]);
+        assert_eq!(a * Vec3f::new([1., 0., -1.]), Vec2f::new([-2., -2.]));
+    }
+
+    #[test]
+    fn determinant() {
+        let m = Mat3x3::from_rows([
+            Vec3f::new([6., 1., 1.]),
+            Vec3f::new([4., -2., 5.]),
+            Vec3f::new([2., 8., 7.]),
+        ]);
+        assert!((m.determinant() - -306.).abs() < 1e-9);
+
+        let swapped = Mat2x2::from_rows([Vec2f::new([0., 1.]), Vec2f::new([1., 0.])]);
+        assert_eq!(swapped.determinant(), -1.);
+
+        assert_eq!(Mat4x4::identity().determinant(), 1.);
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Mat3x3::from_rows([
+            Vec3f::new([2., 0., 0.]),
+            Vec3f::new([0., 0., 4.]),
+            Vec3f::new([0., 1., 0.]),
+        ]);
+        let expected = Mat3x3::from_rows([
+            Vec3f::new([0.5, 0., 0.]),
+            Vec3f::new([0., 0., 1.]),
+            Vec3f::new([0., 0.25, 0.]),
+        ]);
+
+        let inv = m.inverse().unwrap();
+        assert_close(inv, expected);
+        assert_close(m * inv, Mat3x3::identity());
+    }
+
+    #[test]
+    fn singular_has_no_inverse() {
+        let m = Mat3x3::from_rows([
+            Vec3f::new([1., 2., 3.]),
+            Vec3f::new([2., 4., 6.]),
+            Vec3f::new([0., 1., 1.]),
+        ]);
+
+        assert_eq!(m.determinant(), 0.);
+        assert_eq!(m.inverse(), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::math::{Mat4x4, Vec2i, Vec3f, Vec4f};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians.
+    Perspective { fov_y: f64, near: f64, far: f64 },
+    /// `height` is the extent of the view volume in world units.
+    Orthographic { height: f64, near: f64, far: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub eye: Vec3f,
+    pub target: Vec3f,
+    pub up: Vec3f,
+
+    pub projection: Projection,
+    /// Projects the near plane to depth 1 and the far plane to 0, to be depth tested with
+    /// [`super::DepthCompare::Greater`].
+    pub reversed_z: bool,
+}
+
+impl Camera {
+    pub fn new(eye: Vec3f, target: Vec3f, up: Vec3f, projection: Projection) -> Self {
+        Self {
+            eye,
+            target,
+            up,
+            projection,
+            reversed_z: false,
+        }
+    }
+
+    pub fn view(&self) -> Mat4x4 {
+        look_at(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self, aspect: f64) -> Mat4x4 {
+        let m = match self.projection {
+            Projection::Perspective { fov_y, near, far } => perspective(fov_y, aspect, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let (hw, hh) = (height * aspect * 0.5, height * 0.5);
+                orthographic(-hw, hw, -hh, hh, near, far)
+            }
+        };
+
+        if self.reversed_z {
+            reverse_z(m)
+        } else {
+            m
+        }
+    }
+
+    /// Projection times view; multiply by a model matrix on the right to get the full MVP.
+    pub fn view_projection(&self, aspect: f64) -> Mat4x4 {
+        self.projection(aspect) * self.view()
+    }
+}
+
+/// Right-handed view matrix looking from `eye` towards `target`.
+pub fn look_at(eye: Vec3f, target: Vec3f, up: Vec3f) -> Mat4x4 {
+    let f = (target - eye).normalized();
+    let s = f.cross(up).normalized();
+    let u = s.cross(f);
+
+    Mat4x4::from_rows([
+        Vec4f::new([s.x(), s.y(), s.z(), -s.dot(eye)]),
+        Vec4f::new([u.x(), u.y(), u.z(), -u.dot(eye)]),
+        Vec4f::new([-f.x(), -f.y(), -f.z(), f.dot(eye)]),
+        Vec4f::new([0., 0., 0., 1.]),
+    ])
+}
+
+/// Maps the view frustum onto the `[-1, 1]` clip cube, OpenGL style.
+pub fn perspective(fov_y: f64, aspect: f64, near: f64, far: f64) -> Mat4x4 {
+    let f = 1. / f64::tan(fov_y * 0.5);
+    let d = near - far;
+
+    Mat4x4::from_rows([
+        Vec4f::new([f / aspect, 0., 0., 0.]),
+        Vec4f::new([0., f, 0., 0.]),
+        Vec4f::new([0., 0., (far + near) / d, 2. * far * near / d]),
+        Vec4f::new([0., 0., -1., 0.]),
+    ])
+}
+
+pub fn orthographic(left: f64, right: f64, bottom: f64, top: f64, near: f64, far: f64) -> Mat4x4 {
+    let (w, h, d) = (right - left, top - bottom, far - near);
+
+    Mat4x4::from_rows([
+        Vec4f::new([2. / w, 0., 0., -(right + left) / w]),
+        Vec4f::new([0., 2. / h, 0., -(top + bottom) / h]),
+        Vec4f::new([0., 0., -2. / d, -(far + near) / d]),
+        Vec4f::new([0., 0., 0., 1.]),
+    ])
+}
+
+/// Flips the depth of `projection`, so the near plane maps to the far side of the clip cube
+/// and the other way around.
+pub fn reverse_z(projection: Mat4x4) -> Mat4x4 {
+    let flip = Mat4x4::from_rows([
+        Vec4f::new([1., 0., 0., 0.]),
+        Vec4f::new([0., 1., 0., 0.]),
+        Vec4f::new([0., 0., -1., 0.]),
+        Vec4f::new([0., 0., 0., 1.]),
+    ]);
+    flip * projection
+}
+
+/// Maps normalized device coordinates to pixels, flipping y so it points down,
+/// and depth to `[0, 1]` with 0 at the near plane.
+pub fn viewport(size: Vec2i) -> Mat4x4 {
+    let (hw, hh) = (size.width() as f64 * 0.5, size.height() as f64 * 0.5);
+
+    Mat4x4::from_rows([
+        Vec4f::new([hw, 0., 0., hw]),
+        Vec4f::new([0., -hh, 0., hh]),
+        Vec4f::new([0., 0., 0.5, 0.5]),
+        Vec4f::new([0., 0., 0., 1.]),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversed_z() {
+        let mut camera = Camera::new(
+            Vec3f::zero(),
+            Vec3f::new([0., 0., -1.]),
+            Vec3f::new([0., 1., 0.]),
+            Projection::Perspective {
+                fov_y: 1.,
+                near: 0.5,
+                far: 20.,
+            },
+        );
+        let viewport = viewport(Vec2i::new([4, 4]));
+        let depth = |camera: &Camera, z: f64| {
+            let p = viewport * camera.view_projection(1.) * Vec4f::new([0., 0., -z, 1.]);
+            p.z() / p.w()
+        };
+
+        camera.reversed_z = true;
+        assert!((depth(&camera, 0.5) - 1.).abs() < 1e-12);
+        assert!(depth(&camera, 20.).abs() < 1e-12);
+        let (near, far) = (depth(&camera, 2.), depth(&camera, 3.));
+        assert!(near > far);
+
+        camera.reversed_z = false;
+        assert!((depth(&camera, 2.) - (1. - near)).abs() < 1e-12);
+    }
+}
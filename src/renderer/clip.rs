@@ -0,0 +1,90 @@
+use super::shader::Varying;
+use crate::math::Vec4f;
+
+/// How far past the screen edges, in multiples of the screen's half size, triangles may reach
+/// before being cut. Everything in the guard band is left to the rasterizer, which only walks
+/// on-screen pixels anyway, so most triangles poking out of the screen need no clipping.
+pub const GUARD_BAND: f64 = 2.;
+
+/// The planes of the clip volume, as `a` with `a.dot(v) >= 0` for clip-space points `v` inside.
+fn planes(guard_band: f64) -> [Vec4f; 6] {
+    [
+        // near and far
+        Vec4f::new([0., 0., 1., 1.]),
+        Vec4f::new([0., 0., -1., 1.]),
+        // left, right, bottom and top
+        Vec4f::new([1., 0., 0., guard_band]),
+        Vec4f::new([-1., 0., 0., guard_band]),
+        Vec4f::new([0., 1., 0., guard_band]),
+        Vec4f::new([0., -1., 0., guard_band]),
+    ]
+}
+
+/// Clips a clip-space triangle and its varyings to the view frustum, with the side planes
+/// pushed out by `guard_band`, and fans the result back into triangles.
+///
+/// Returns no triangles if it is entirely outside. New vertices get their varyings
+/// interpolated linearly in clip space.
+pub fn clip_triangle<V: Varying>(tri: [(Vec4f, V); 3], guard_band: f64) -> Vec<[(Vec4f, V); 3]> {
+    if !crosses(&tri, guard_band) {
+        return vec![tri];
+    }
+
+    let mut poly = tri.to_vec();
+    for plane in planes(guard_band) {
+        poly = clip_polygon(&poly, plane);
+        if poly.is_empty() {
+            return vec![];
+        }
+    }
+
+    (1..poly.len() - 1)
+        .map(|i| [poly[0], poly[i], poly[i + 1]])
+        .collect()
+}
+
+/// Whether any corner of `tri` is outside the clip volume, so [`clip_triangle`] has to cut it.
+pub fn crosses<V>(tri: &[(Vec4f, V); 3], guard_band: f64) -> bool {
+    let planes = planes(guard_band);
+    tri.iter()
+        .any(|(p, _)| planes.iter().any(|plane| plane.dot(*p) < 0.))
+}
+
+/// One step of Sutherland-Hodgman, keeping the part of `poly` in front of `plane`.
+fn clip_polygon<V: Varying>(poly: &[(Vec4f, V)], plane: Vec4f) -> Vec<(Vec4f, V)> {
+    let mut out = Vec::with_capacity(poly.len() + 1);
+
+    for (i, &a) in poly.iter().enumerate() {
+        let b = poly[(i + 1) % poly.len()];
+        let (da, db) = (plane.dot(a.0), plane.dot(b.0));
+
+        if da >= 0. {
+            out.push(a);
+        }
+        if (da >= 0.) != (db >= 0.) {
+            out.push(lerp(a, b, da / (da - db)));
+        }
+    }
+
+    out
+}
+
+/// Clips a clip-space line segment like [`clip_triangle`], `None` if none of it is visible.
+pub fn clip_line(mut a: Vec4f, mut b: Vec4f, guard_band: f64) -> Option<(Vec4f, Vec4f)> {
+    for plane in planes(guard_band) {
+        let (da, db) = (plane.dot(a), plane.dot(b));
+
+        match (da >= 0., db >= 0.) {
+            (true, true) => {}
+            (false, false) => return None,
+            (true, false) => b = a + (b - a) * (da / (da - db)),
+            (false, true) => a = a + (b - a) * (da / (da - db)),
+        }
+    }
+
+    Some((a, b))
+}
+
+fn lerp<V: Varying>(a: (Vec4f, V), b: (Vec4f, V), t: f64) -> (Vec4f, V) {
+    (a.0 * (1. - t) + b.0 * t, a.1 * (1. - t) + b.1 * t)
+}
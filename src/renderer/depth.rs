@@ -0,0 +1,144 @@
+use crate::math::Vec2i;
+
+/// When a fragment's depth passes against what the buffer holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthCompare {
+    /// Nearer fragments win with regular depth.
+    #[default]
+    Less,
+    /// Nearer fragments win with reversed-Z.
+    Greater,
+    /// Every fragment passes, later ones overwrite earlier ones.
+    Always,
+}
+
+impl DepthCompare {
+    pub fn passes(self, new: f64, old: f64) -> bool {
+        match self {
+            DepthCompare::Less => new < old,
+            DepthCompare::Greater => new > old,
+            DepthCompare::Always => true,
+        }
+    }
+
+    /// The value that every depth passes against.
+    fn cleared(self) -> f64 {
+        match self {
+            DepthCompare::Less | DepthCompare::Always => f64::INFINITY,
+            DepthCompare::Greater => f64::NEG_INFINITY,
+        }
+    }
+}
+
+/// Depth per pixel of a render target, sized to match it.
+///
+/// Fragment depths are in `[0, 1]`, with 0 at the near plane unless the camera has
+/// [`super::Camera::reversed_z`], which needs [`DepthCompare::Greater`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DepthBuffer {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+
+    compare: DepthCompare,
+}
+
+impl DepthBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_compare(width, height, DepthCompare::Less)
+    }
+
+    /// A buffer for reversed-Z, keeping the nearest fragments.
+    pub fn reversed(width: usize, height: usize) -> Self {
+        Self::with_compare(width, height, DepthCompare::Greater)
+    }
+
+    pub fn with_compare(width: usize, height: usize, compare: DepthCompare) -> Self {
+        Self {
+            width,
+            height,
+            values: vec![compare.cleared(); width * height],
+
+            compare,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn compare(&self) -> DepthCompare {
+        self.compare
+    }
+
+    /// Changes how depths are compared, clearing the buffer.
+    pub fn set_compare(&mut self, compare: DepthCompare) {
+        self.compare = compare;
+        self.clear();
+    }
+
+    /// Reallocates and clears the buffer if its size differs from `width` by `height`.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.width, self.height) {
+            *self = Self::with_compare(width, height, self.compare);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(self.compare.cleared());
+    }
+
+    pub fn index(&self, pos: Vec2i) -> Option<usize> {
+        let (x, y) = (
+            usize::try_from(pos.x()).ok()?,
+            usize::try_from(pos.y()).ok()?,
+        );
+        (x < self.width && y < self.height).then_some(x + y * self.width)
+    }
+
+    /// Depth at `pos`.
+    pub fn get(&self, pos: Vec2i) -> Option<f64> {
+        self.index(pos).map(|i| self.values[i])
+    }
+
+    /// Depths in row-major order, top row first.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Whether a fragment at `depth` passes against the value at index `i`.
+    pub fn test(&self, i: usize, depth: f64) -> bool {
+        self.compare.passes(depth, self.values[i])
+    }
+
+    pub fn set(&mut self, i: usize, depth: f64) {
+        self.values[i] = depth;
+    }
+
+    /// The buffer split into bands of `rows` rows each, top band first.
+    pub(super) fn bands_mut(&mut self, rows: usize) -> impl Iterator<Item = DepthRows<'_>> {
+        let compare = self.compare;
+        self.values
+            .chunks_mut((self.width * rows).max(1))
+            .map(move |values| DepthRows { values, compare })
+    }
+}
+
+/// Some whole rows of a [`DepthBuffer`], indexed from the first of them.
+pub(super) struct DepthRows<'a> {
+    values: &'a mut [f64],
+    compare: DepthCompare,
+}
+
+impl DepthRows<'_> {
+    pub fn test(&self, i: usize, depth: f64) -> bool {
+        self.compare.passes(depth, self.values[i])
+    }
+
+    pub fn set(&mut self, i: usize, depth: f64) {
+        self.values[i] = depth;
+    }
+}
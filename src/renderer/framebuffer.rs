@@ -0,0 +1,127 @@
+use std::convert::Infallible;
+
+use super::{DepthBuffer, Img, ImgColor};
+use crate::math::{Vec2i, Vec3f};
+
+/// In-memory render target with a color and a depth plane, usable without a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer<C: ImgColor> {
+    width: usize,
+    height: usize,
+
+    color: Vec<C>,
+    depth: DepthBuffer,
+}
+
+impl<C: ImgColor> Framebuffer<C> {
+    pub fn new(width: usize, height: usize, clear_color: C) -> Self {
+        Self {
+            width,
+            height,
+
+            color: vec![clear_color; width * height],
+            depth: DepthBuffer::new(width, height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, pos: Vec2i) -> Option<usize> {
+        let (x, y) = (
+            usize::try_from(pos.x()).ok()?,
+            usize::try_from(pos.y()).ok()?,
+        );
+        (x < self.width && y < self.height).then_some(x + y * self.width)
+    }
+
+    pub fn get_px(&self, pos: Vec2i) -> Option<&C> {
+        self.index(pos).map(|i| &self.color[i])
+    }
+    pub fn get_depth(&self, pos: Vec2i) -> Option<f64> {
+        self.depth.get(pos)
+    }
+
+    /// Color plane in row-major order, top row first.
+    pub fn pixels(&self) -> &[C] {
+        &self.color
+    }
+    pub fn depth(&self) -> &DepthBuffer {
+        &self.depth
+    }
+    pub fn depth_mut(&mut self) -> &mut DepthBuffer {
+        &mut self.depth
+    }
+    pub(super) fn planes_mut(&mut self) -> (&mut [C], &mut DepthBuffer) {
+        (&mut self.color, &mut self.depth)
+    }
+
+    /// Reallocates both planes if the size changed, clearing them to `clear_color`.
+    pub fn resize(&mut self, width: usize, height: usize, clear_color: C) {
+        if (width, height) != (self.width, self.height) {
+            (self.width, self.height) = (width, height);
+            self.color = vec![clear_color; width * height];
+            self.depth.resize(width, height);
+        }
+    }
+
+    /// Color plane as 8-bit RGBA, e.g. for [`super::tga::write_rgba`].
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.color
+            .iter()
+            .flat_map(|c| {
+                let (r, g, b, a) = c.clone().into();
+                [r, g, b, a]
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self, color: C) {
+        self.clear_color(color);
+        self.clear_depth();
+    }
+    pub fn clear_color(&mut self, color: C) {
+        self.color.fill(color);
+    }
+    pub fn clear_depth(&mut self) {
+        self.depth.clear();
+    }
+}
+
+impl<C: ImgColor> Img for Framebuffer<C> {
+    type Color = C;
+    type Err = Infallible;
+
+    fn set_px(&mut self, pos: Vec2i, color: Self::Color) -> Result<(), Self::Err> {
+        if let Some(i) = self.index(pos) {
+            self.color[i] = color;
+        }
+        Ok(())
+    }
+
+    /// Depth tests against the framebuffer's own depth plane unless another buffer is given.
+    fn tri(
+        &mut self,
+        tri: [Vec3f; 3],
+        color: Self::Color,
+        depth: Option<&mut DepthBuffer>,
+    ) -> Result<(), Self::Err> {
+        if depth.is_some() {
+            return super::tri(self, tri, color, depth);
+        }
+
+        let mut depth = std::mem::take(&mut self.depth);
+        let res = super::tri(self, tri, color, Some(&mut depth));
+        self.depth = depth;
+
+        res
+    }
+
+    fn size(&self) -> Result<Vec2i, Self::Err> {
+        Ok(Vec2i::new([self.width as i32, self.height as i32]))
+    }
+}
@@ -0,0 +1,199 @@
+use crate::math::Vec3f;
+
+/// Falloff of point and spot lights, `1 / (constant + linear * d + quadratic * d²)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    pub const NONE: Self = Self::new(1., 0., 0.);
+
+    pub const fn new(constant: f64, linear: f64, quadratic: f64) -> Self {
+        Self {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    pub fn at(&self, distance: f64) -> f64 {
+        1. / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Infinitely far away, `direction` is where the light travels.
+    Directional { direction: Vec3f },
+    Point {
+        position: Vec3f,
+        attenuation: Attenuation,
+    },
+    /// A point light restricted to a cone. The angles are measured from `direction` in radians,
+    /// the light fades out between `inner` and `outer`.
+    Spot {
+        position: Vec3f,
+        direction: Vec3f,
+        inner: f64,
+        outer: f64,
+        attenuation: Attenuation,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Vec3f,
+    pub intensity: f64,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3f, color: Vec3f, intensity: f64) -> Self {
+        Self {
+            kind: LightKind::Directional {
+                direction: direction.normalized(),
+            },
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(position: Vec3f, attenuation: Attenuation, color: Vec3f, intensity: f64) -> Self {
+        Self {
+            kind: LightKind::Point {
+                position,
+                attenuation,
+            },
+            color,
+            intensity,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3f,
+        direction: Vec3f,
+        (inner, outer): (f64, f64),
+        attenuation: Attenuation,
+        color: Vec3f,
+        intensity: f64,
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                position,
+                direction: direction.normalized(),
+                inner,
+                outer,
+                attenuation,
+            },
+            color,
+            intensity,
+        }
+    }
+
+    /// Unit vector from `pos` towards the light and the light arriving there.
+    pub fn incident(&self, pos: Vec3f) -> (Vec3f, Vec3f) {
+        let radiance = self.color * self.intensity;
+
+        match self.kind {
+            LightKind::Directional { direction } => (-direction, radiance),
+            LightKind::Point {
+                position,
+                attenuation,
+            } => {
+                let (l, d) = towards(pos, position);
+                (l, radiance * attenuation.at(d))
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                inner,
+                outer,
+                attenuation,
+            } => {
+                let (l, d) = towards(pos, position);
+
+                let (cos_inner, cos_outer) = (inner.cos(), outer.cos());
+                let cos = (-l).dot(direction);
+                let cone = if cos_inner > cos_outer {
+                    smoothstep((cos - cos_outer) / (cos_inner - cos_outer))
+                } else if cos >= cos_outer {
+                    1.
+                } else {
+                    0.
+                };
+
+                (l, radiance * (attenuation.at(d) * cone))
+            }
+        }
+    }
+}
+
+fn towards(from: Vec3f, to: Vec3f) -> (Vec3f, f64) {
+    let d = to - from;
+    let len = d.len();
+    (if len > 0. { d / len } else { d }, len)
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// A surface point and its colors as seen by [`blinn_phong`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    pub pos: Vec3f,
+    /// Unit length.
+    pub normal: Vec3f,
+
+    pub ambient: Vec3f,
+    pub diffuse: Vec3f,
+    pub specular: Vec3f,
+    pub shininess: f64,
+}
+
+/// Ambient plus Lambert diffuse plus Blinn-Phong specular from every light in `lights`.
+///
+/// `eye` is the viewer's position. `visibility` tells how much of the light with the given
+/// index reaches the surface, e.g. from a shadow map.
+pub fn blinn_phong(
+    lights: &[Light],
+    ambient_light: Vec3f,
+    s: &Surface,
+    eye: Vec3f,
+    visibility: impl Fn(usize) -> f64,
+) -> Vec3f {
+    let (v, _) = towards(s.pos, eye);
+    let mut c = mul(s.ambient, ambient_light);
+
+    for (i, light) in lights.iter().enumerate() {
+        let (l, radiance) = light.incident(s.pos);
+
+        let n_dot_l = s.normal.dot(l);
+        if n_dot_l <= 0. {
+            continue;
+        }
+        let shadow = visibility(i);
+        if shadow <= 0. {
+            continue;
+        }
+
+        let h = l + v;
+        let spec = if h.len_sqr() > 0. {
+            s.normal.dot(h.normalized()).max(0.).powf(s.shininess)
+        } else {
+            0.
+        };
+
+        c += mul(s.diffuse * n_dot_l + s.specular * spec, radiance * shadow);
+    }
+
+    c
+}
+
+fn mul(a: Vec3f, b: Vec3f) -> Vec3f {
+    Vec3f::new([a.x() * b.x(), a.y() * b.y(), a.z() * b.z()])
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use super::obj::{FaceInd, Idx, ObjData};
+use crate::math::{Vec2f, Vec3f, Vec4f};
+
+/// How much each face contributes to the normals of its corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// By face area, so big faces dominate.
+    Area,
+    /// By the angle the face makes at the corner, independent of tessellation.
+    Angle,
+}
+
+/// Computes smooth vertex normals, replacing `vertex_normals` and the `vn` of every corner.
+///
+/// A corner's normal averages the faces around its vertex that share its face's smoothing
+/// group and, given a `crease_angle` in radians, bend away from its face by less than it.
+/// Faces with smoothing group 0 stay flat, unless the mesh has no smoothing groups at all.
+pub fn generate_normals(o: &mut ObjData, weighting: NormalWeighting, crease_angle: Option<f64>) {
+    let smooth_all = o.smoothing_groups.iter().all(|&s| s == 0);
+    let min_cos = crease_angle.map_or(f64::NEG_INFINITY, f64::cos);
+
+    let mut face_normals = Vec::with_capacity(o.faces.len());
+    // weight of each face at each of its corners
+    let mut weights = Vec::with_capacity(o.faces.len());
+    // faces and corners around each vertex
+    let mut incident: Vec<Vec<(usize, usize)>> = vec![vec![]; o.vertices.len()];
+
+    for (fi, f) in o.faces.iter().enumerate() {
+        let [a, b, c] = f.map(|i| o.vertex(i).div_w());
+
+        // twice the area, pointing along the normal
+        let n = (b - a).cross(c - a);
+        let area = n.len() * 0.5;
+        let n = if area > 0. { n.normalized() } else { n };
+        face_normals.push(n);
+
+        weights.push(match weighting {
+            NormalWeighting::Area => [area; 3],
+            NormalWeighting::Angle => [
+                corner_angle(c, a, b),
+                corner_angle(a, b, c),
+                corner_angle(b, c, a),
+            ],
+        });
+
+        for (ci, corner) in f.iter().enumerate() {
+            incident[corner.v as usize - 1].push((fi, ci));
+        }
+    }
+
+    let smooths = |a: usize, b: usize| {
+        if a == b {
+            return true;
+        }
+
+        let (sa, sb) = (o.smoothing_group(a), o.smoothing_group(b));
+        let same_group = if smooth_all {
+            true
+        } else {
+            sa != 0 && sa == sb
+        };
+
+        same_group && face_normals[a].dot(face_normals[b]) >= min_cos
+    };
+
+    let mut normals = vec![];
+    let mut lookup: HashMap<[u64; 3], Idx> = HashMap::new();
+    let mut corner_normals: Vec<[Idx; 3]> = Vec::with_capacity(o.faces.len());
+
+    for (fi, f) in o.faces.iter().enumerate() {
+        corner_normals.push(f.map(|c| {
+            let mut n = Vec3f::zero();
+            for &(fj, cj) in &incident[c.v as usize - 1] {
+                if smooths(fi, fj) {
+                    n += face_normals[fj] * weights[fj][cj];
+                }
+            }
+            let n = if n.len_sqr() > 0. {
+                n.normalized()
+            } else {
+                face_normals[fi]
+            };
+
+            *lookup.entry(n.map(f64::to_bits)).or_insert_with(|| {
+                normals.push(n);
+                normals.len() as Idx
+            })
+        }));
+    }
+
+    for (f, indices) in o.faces.iter_mut().zip(corner_normals) {
+        for (c, vn) in f.iter_mut().zip(indices) {
+            c.vn = Some(vn);
+        }
+    }
+
+    o.vertex_normals = normals;
+}
+
+/// Normal of a polygon, twice as long as its area.
+fn newell(pos: &[Vec3f]) -> Vec3f {
+    let mut normal = Vec3f::zero();
+    for (i, a) in pos.iter().enumerate() {
+        let b = pos[(i + 1) % pos.len()];
+        normal += Vec3f::new([
+            (a.y() - b.y()) * (a.z() + b.z()),
+            (a.z() - b.z()) * (a.x() + b.x()),
+            (a.x() - b.x()) * (a.y() + b.y()),
+        ]);
+    }
+    normal
+}
+
+fn corner_angle(prev: Vec3f, at: Vec3f, next: Vec3f) -> f64 {
+    let (a, b) = (prev - at, next - at);
+    let len = a.len() * b.len();
+    if len == 0. {
+        return 0.;
+    }
+
+    f64::acos((a.dot(b) / len).clamp(-1., 1.))
+}
+
+/// Splits a planar polygon, convex or concave, into triangles by ear clipping.
+///
+/// The triangles keep the polygon's winding and each corner's `vt`/`vn` indices.
+/// Polygons referencing unknown vertices or with no ear left (self-intersecting ones)
+/// fall back to a triangle fan.
+pub fn triangulate(vertices: &[Vec4f], poly: &[FaceInd]) -> Vec<[FaceInd; 3]> {
+    if poly.len() < 3 {
+        return vec![];
+    }
+
+    let Some(pos) = poly
+        .iter()
+        .map(|i| {
+            vertices
+                .get((i.v as usize).wrapping_sub(1))
+                .map(|v| v.div_w())
+        })
+        .collect::<Option<Vec<_>>>()
+    else {
+        return fan(poly);
+    };
+
+    let pts = project_to_plane(&pos);
+
+    // twice the signed area, tells which way round the polygon goes in 2D
+    let winding = (0..pts.len())
+        .map(|i| cross(pts[i], pts[(i + 1) % pts.len()]))
+        .sum::<f64>()
+        .signum();
+
+    let mut remaining: Vec<usize> = (0..poly.len()).collect();
+    let mut tris = Vec::with_capacity(poly.len() - 2);
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+
+        let ear = (0..n).find(|&i| {
+            let [a, b, c] = [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ];
+            let t = [pts[a], pts[b], pts[c]];
+
+            if cross(t[1] - t[0], t[2] - t[1]) * winding <= 0. {
+                return false;
+            }
+
+            remaining
+                .iter()
+                .filter(|&&j| j != a && j != b && j != c)
+                .all(|&j| pts[j] == t[0] || pts[j] == t[1] || pts[j] == t[2] || !inside(t, pts[j]))
+        });
+
+        let Some(i) = ear else {
+            let rest: Vec<_> = remaining.iter().map(|&j| poly[j]).collect();
+            tris.extend(fan(&rest));
+            return tris;
+        };
+
+        tris.push([
+            poly[remaining[(i + n - 1) % n]],
+            poly[remaining[i]],
+            poly[remaining[(i + 1) % n]],
+        ]);
+        remaining.remove(i);
+    }
+
+    tris.push([poly[remaining[0]], poly[remaining[1]], poly[remaining[2]]]);
+    tris
+}
+
+fn fan(poly: &[FaceInd]) -> Vec<[FaceInd; 3]> {
+    (1..poly.len().saturating_sub(1))
+        .map(|i| [poly[0], poly[i], poly[i + 1]])
+        .collect()
+}
+
+/// Drops the axis the polygon's Newell normal points along the most.
+fn project_to_plane(pos: &[Vec3f]) -> Vec<Vec2f> {
+    let n = Vec3f::new(newell(pos).map(f64::abs));
+    let (u, v) = if n.x() >= n.y() && n.x() >= n.z() {
+        (1, 2)
+    } else if n.y() >= n.z() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+
+    pos.iter().map(|p| Vec2f::new([p[u], p[v]])).collect()
+}
+
+fn cross(a: Vec2f, b: Vec2f) -> f64 {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+/// Whether `p` is inside or on the edges of `t`, regardless of its winding.
+fn inside(t: [Vec2f; 3], p: Vec2f) -> bool {
+    let d = [
+        cross(t[1] - t[0], p - t[0]),
+        cross(t[2] - t[1], p - t[1]),
+        cross(t[0] - t[2], p - t[2]),
+    ];
+
+    d.iter().all(|&d| d >= 0.) || d.iter().all(|&d| d <= 0.)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3f,
+    pub radius: f64,
+}
+
+/// A sphere around all `vertices`, centered on their bounding box. Not the smallest one,
+/// but close enough for culling.
+pub fn bounding_sphere(vertices: &[Vec4f]) -> Sphere {
+    let pos: Vec<_> = vertices.iter().map(|v| v.div_w()).collect();
+    if pos.is_empty() {
+        return Sphere {
+            center: Vec3f::zero(),
+            radius: 0.,
+        };
+    }
+
+    let (mut min, mut max) = (pos[0], pos[0]);
+    for p in &pos {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+
+    let center = (min + max) * 0.5;
+    let radius = pos.iter().map(|&p| (p - center).len()).fold(0., f64::max);
+
+    Sphere { center, radius }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::renderer::obj;
+
+    /// Normals at the corner on vertex 1 of the two faces of a right-angled fold, the first
+    /// face facing `z` and the second `y`.
+    fn fold_normals(groups: [&str; 2], crease_angle: Option<f64>) -> [Vec3f; 2] {
+        let fold = format!(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n{}\nf 1 2 3\n{}\nf 1 4 2\n",
+            groups[0], groups[1]
+        );
+        let mut o = obj::read(fold.as_bytes().lines()).unwrap();
+        generate_normals(&mut o, NormalWeighting::Angle, crease_angle);
+
+        [0, 1].map(|f| o.vertex_normal(o.faces[f][0]).unwrap())
+    }
+
+    #[test]
+    fn creases_and_smoothing_groups() {
+        let (z, y) = (Vec3f::new([0., 0., 1.]), Vec3f::new([0., 1., 0.]));
+        let close = |a: Vec3f, b: Vec3f| (a - b).len() < 1e-9;
+
+        // no groups at all smooths everything
+        let [a, b] = fold_normals(["", ""], None);
+        assert_eq!(a, b);
+        assert!(close(a, (y + z).normalized()));
+
+        for (groups, crease_angle) in [
+            (["", ""], Some(0.5)),
+            (["s 1", "s 2"], None),
+            // group 0 stays flat once there are other groups
+            (["s 1", "s off"], None),
+        ] {
+            let [a, b] = fold_normals(groups, crease_angle);
+            assert!(close(a, z) && close(b, y), "{groups:?} {crease_angle:?}");
+        }
+
+        let [a, b] = fold_normals(["s 1", "s 1"], Some(2.));
+        assert_eq!(a, b);
+    }
+}
@@ -1,9 +1,35 @@
-use crate::math::{vec::Vec2i, Vec2f, Vec3f};
+use std::ops::Range;
 
-use self::obj::{Face, ObjData};
+use crate::math::{vec::Vec2i, Mat4x4, Vec3f, Vec4f};
 
+use self::obj::ObjData;
+
+pub mod camera;
+pub mod clip;
+pub mod depth;
+pub mod framebuffer;
+pub mod light;
+pub mod mesh;
+pub mod mtl;
 pub mod obj;
-// pub mod tga;
+pub mod pipeline;
+pub mod shader;
+pub mod shadow;
+pub mod texture;
+pub mod tga;
+pub mod tiled;
+
+pub use camera::{Camera, Projection};
+pub use depth::{DepthBuffer, DepthCompare};
+pub use framebuffer::Framebuffer;
+pub use light::{Attenuation, Light, LightKind};
+pub use pipeline::{CullMode, Pipeline, Stats};
+pub use shader::{
+    BlinnPhongShader, FlatShader, Fragment, GouraudShader, NormalMap, PhongShader, Shader,
+    TexturedShader, Varying,
+};
+pub use shadow::ShadowMap;
+pub use texture::{Filter, Sampler, Texture, Wrap};
 
 pub trait ImgColor:
     std::fmt::Debug
@@ -32,9 +58,9 @@ pub trait Img: Sized {
         &mut self,
         tri: [Vec3f; 3],
         color: Self::Color,
-        zbuffer: Option<&mut [f64]>,
+        depth: Option<&mut DepthBuffer>,
     ) -> Result<(), Self::Err> {
-        crate::renderer::tri(self, tri, color, zbuffer)
+        crate::renderer::tri(self, tri, color, depth)
     }
 
     fn size(&self) -> Result<Vec2i, Self::Err>;
@@ -76,142 +102,692 @@ pub fn line<I: Img>(c: &mut I, mut s: Vec2i, mut e: Vec2i, color: I::Color) -> R
     Ok(())
 }
 
-pub fn flat_shaded<I: Img>(
+/// Clip space to screen space: pixels for `x`/`y`, depth in `[0, 1]` for `z`.
+/// Only meaningful for points that survived clipping, which have a positive `w`.
+fn project(viewport: &Mat4x4, clip: Vec4f) -> Vec3f {
+    (*viewport * clip).div_w()
+}
+
+/// Runs `shader` over every face of `o`.
+pub fn draw<I: Img, S: Shader<I::Color>>(
     img: &mut I,
-    size: Vec2i,
-    offs: Vec3f,
     o: &ObjData,
-    light_dir: Vec3f,
-    color: I::Color,
-    mut zbuffer: Option<&mut [f64]>,
+    shader: &S,
+    pipeline: &mut Pipeline,
+    depth: Option<&mut DepthBuffer>,
 ) -> Result<(), I::Err> {
-    let (cw, ch) = (size.width() as f64 * 0.5, size.height() as f64 * 0.5);
+    draw_faces(img, o, 0..o.faces.len(), shader, pipeline, depth)
+}
 
-    for f in &o.faces {
-        let Face::Tri(inds) = f else { todo!() };
-        let wc = inds.map(|i| o.vertices[i.v as usize - 1].div_w());
+/// Runs `shader` over the faces of `o` in `faces`, e.g. the ones using a material.
+pub fn draw_faces<I: Img, S: Shader<I::Color>>(
+    img: &mut I,
+    o: &ObjData,
+    faces: Range<usize>,
+    shader: &S,
+    pipeline: &mut Pipeline,
+    mut depth: Option<&mut DepthBuffer>,
+) -> Result<(), I::Err> {
+    let viewport = camera::viewport(img.size()?);
+    let derivatives = shader.needs_derivatives();
+
+    setup(o, faces, shader, pipeline, viewport, |tri| {
+        raster(img, tri.pos, depth.as_deref_mut(), |pos, z, bc| {
+            shader.fragment(tri.fragment(pos, z, bc, derivatives))
+        })
+    })
+}
+
+/// A triangle ready to be rasterized.
+#[derive(Debug, Clone, Copy)]
+struct ScreenTri<V> {
+    /// Pixels for `x`/`y`, depth for `z`.
+    pos: [Vec3f; 3],
+    /// Varyings divided by `w` interpolate linearly on screen, as does `1 / w`.
+    inv_w: [f64; 3],
+    varyings: [V; 3],
+    /// Change of the screen-space barycentric coordinates one pixel right and one down.
+    bc_step: [Vec3f; 2],
+}
+
+impl<V: Varying> ScreenTri<V> {
+    /// The fragment at `pos`, with perspective-correct varyings from the screen-space
+    /// barycentric coordinates `bc`, and their derivatives if asked for.
+    fn fragment(&self, pos: Vec2i, depth: f64, bc: Vec3f, derivatives: bool) -> Fragment<V> {
+        let varying = self.varying(bc);
+        let [dx, dy] = self.bc_step;
+        let (ddx, ddy) = if derivatives {
+            (
+                self.varying(bc + dx) + varying * -1.,
+                self.varying(bc + dy) + varying * -1.,
+            )
+        } else {
+            (varying * 0., varying * 0.)
+        };
+
+        Fragment {
+            pos,
+            depth,
+            varying,
+            ddx,
+            ddy,
+        }
+    }
 
-        let sc = std::array::from_fn(|i| {
-            Vec3f::new([((wc[i].x() + 1.) * cw), ((wc[i].y() + 1.) * ch), 0.]) + offs
-        });
+    fn varying(&self, bc: Vec3f) -> V {
+        let bc: [f64; 3] = std::array::from_fn(|i| bc[i] * self.inv_w[i]);
+        let w = 1. / (bc[0] + bc[1] + bc[2]);
 
-        let normal = (wc[2] - wc[0]).cross(wc[1] - wc[0]).normalized();
+        let v = self.varyings;
+        v[0] * (bc[0] * w) + v[1] * (bc[1] * w) + v[2] * (bc[2] * w)
+    }
+}
 
-        let int = normal.dot(light_dir);
-        if int > 0. {
-            let (r, g, b, a): (f64, f64, f64, f64) = color.clone().into();
+/// Runs the vertex shader over `faces`, culls and clips the triangles and hands what is
+/// left to `raster` in screen space, counting them all in `pipeline`'s stats.
+fn setup<S: Shader<C>, C, E>(
+    o: &ObjData,
+    faces: Range<usize>,
+    shader: &S,
+    pipeline: &mut Pipeline,
+    viewport: Mat4x4,
+    mut raster: impl FnMut(ScreenTri<S::Varying>) -> Result<(), E>,
+) -> Result<(), E> {
+    let stats = &mut pipeline.stats;
+
+    for inds in &o.faces[faces] {
+        stats.submitted += 1;
+
+        let out: [_; 3] = std::array::from_fn(|i| shader.vertex(o, inds, i));
+        if pipeline.cull.culls(out.map(|(clip, _)| clip)) {
+            stats.culled += 1;
+            continue;
+        }
 
-            img.tri(
-                sc,
-                (int * r, int * g, int * b, a).into(),
-                zbuffer.as_deref_mut(),
-            )?;
+        let tris = if clip::crosses(&out, clip::GUARD_BAND) {
+            let tris = clip::clip_triangle(out, clip::GUARD_BAND);
+            if tris.is_empty() {
+                stats.culled += 1;
+            } else {
+                stats.clipped += 1;
+            }
+            tris
+        } else {
+            vec![out]
+        };
+
+        for tri in tris {
+            stats.rasterized += 1;
+
+            let pos = tri.map(|(clip, _)| project(&viewport, clip));
+            raster(ScreenTri {
+                pos,
+                inv_w: tri.map(|(clip, _)| 1. / clip.w()),
+                varyings: tri.map(|(_, v)| v),
+                bc_step: bc_step(pos),
+            })?;
         }
     }
 
     Ok(())
 }
 
-pub fn wireframe<I: Img>(
+/// Lit by `light_dir` in the model's own coordinates.
+pub fn flat_shaded<I: Img>(
     img: &mut I,
-    size: Vec2i,
-    offs: Vec2i,
+    mvp: Mat4x4,
     o: &ObjData,
+    light_dir: Vec3f,
     color: I::Color,
+    pipeline: &mut Pipeline,
+    depth: Option<&mut DepthBuffer>,
 ) -> Result<(), I::Err> {
-    let (cw, ch) = (size.width() as f64 * 0.5, size.height() as f64 * 0.5);
-
-    for f in &o.faces {
-        let Face::Tri(inds) = f else { todo!() };
+    let shader = FlatShader {
+        mvp,
+        normal_matrix: Mat4x4::identity(),
+        light_dir,
+        color,
+    };
+    draw(img, o, &shader, pipeline, depth)
+}
 
-        for i in 0..3 {
-            let v0 = inds[i].v;
-            let v1 = inds[(i + 1) % 3].v;
+/// Lit by `light_dir` in the model's own coordinates.
+pub fn textured<I: Img>(
+    img: &mut I,
+    mvp: Mat4x4,
+    o: &ObjData,
+    texture: &Texture,
+    light_dir: Vec3f,
+    pipeline: &mut Pipeline,
+    depth: Option<&mut DepthBuffer>,
+) -> Result<(), I::Err> {
+    let shader = TexturedShader {
+        mvp,
+        normal_matrix: Mat4x4::identity(),
+        light_dir,
+        texture,
+        sampler: Sampler::default(),
+    };
+    draw(img, o, &shader, pipeline, depth)
+}
 
-            let v0 = o.vertices[v0 as usize - 1];
-            let v1 = o.vertices[v1 as usize - 1];
+pub fn wireframe<I: Img>(
+    img: &mut I,
+    mvp: Mat4x4,
+    o: &ObjData,
+    color: I::Color,
+) -> Result<(), I::Err> {
+    let viewport = camera::viewport(img.size()?);
+    let to_px = |v: Vec3f| Vec2i::new([v.x().round() as i32, v.y().round() as i32]);
 
-            let s = Vec2i::new([
-                ((v0.x() + 1.) * cw).round() as i32,
-                ((v0.y() + 1.) * ch).round() as i32,
-            ]);
+    for inds in &o.faces {
+        for i in 0..inds.len() {
+            let v0 = o.vertex(inds[i]);
+            let v1 = o.vertex(inds[(i + 1) % inds.len()]);
 
-            let e = Vec2i::new([
-                ((v1.x() + 1.) * cw).round() as i32,
-                ((v1.y() + 1.) * ch).round() as i32,
-            ]);
+            let Some((s, e)) = clip::clip_line(mvp * v0, mvp * v1, clip::GUARD_BAND) else {
+                continue;
+            };
+            let (s, e) = (project(&viewport, s), project(&viewport, e));
 
-            img.line(offs + s, offs + e, color.clone())?;
+            img.line(to_px(s), to_px(e), color.clone())?;
         }
     }
 
     Ok(())
 }
 
-fn barycentric([a, b, c]: [Vec3f; 3], p: Vec3f) -> Option<Vec3f> {
-    let s: [Vec3f; 2] =
-        std::array::from_fn(|i| Vec3f::new([c[i] - a[i], b[i] - a[i], a[i] - p[i]]));
+fn tri<I: Img>(
+    img: &mut I,
+    tri: [Vec3f; 3],
+    color: I::Color,
+    depth: Option<&mut DepthBuffer>,
+) -> Result<(), I::Err> {
+    raster(img, tri, depth, |_, _, _| Some(color.clone()))
+}
 
-    let u = s[0].cross(s[1]);
-    if f64::abs(u.z()) > 1e-2 {
-        // dont forget that u.z() is integer. If it is zero then triangle ABC is degenerate
-        return Some(Vec3f::new([
-            1. - (u.x() + u.y()) / u.z(),
-            u.y() / u.z(),
-            u.x() / u.z(),
-        ]));
+/// How the barycentric coordinates of `tri` change one pixel right and one pixel down.
+fn bc_step(tri: [Vec3f; 3]) -> [Vec3f; 2] {
+    let area = (tri[1] - tri[0]).cross(tri[2] - tri[0]).z();
+    if area == 0. {
+        return [Vec3f::zero(); 2];
     }
 
-    // in this case generate negative coordinates, it will be thrown away by the rasterizator
-    None
+    // each coordinate is the edge function of the opposite edge over the area
+    let edges = [(tri[1], tri[2]), (tri[2], tri[0]), (tri[0], tri[1])];
+    [
+        Vec3f::new(edges.map(|(a, b)| (a.y() - b.y()) / area)),
+        Vec3f::new(edges.map(|(a, b)| (b.x() - a.x()) / area)),
+    ]
 }
 
-fn tri<I: Img>(
+/// Bits of subpixel precision the rasterizer snaps vertices to.
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL: i64 = 1 << SUBPIXEL_BITS;
+
+/// Twice the signed area of `a`, `b`, `p`, positive if they go clockwise on screen.
+/// Also the edge function of `a`-`b` at `p`.
+fn orient(a: [i64; 2], b: [i64; 2], p: [i64; 2]) -> i64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Rasterizes `tri` in screen space. `shade` gets each covered pixel, its depth and
+/// barycentric coordinates, and may discard the pixel by returning `None`.
+///
+/// With a depth buffer, pixels failing its depth test or outside of it are skipped.
+fn raster<I: Img>(
     img: &mut I,
     tri: [Vec3f; 3],
-    color: I::Color,
-    mut zbuffer: Option<&mut [f64]>,
+    mut depth: Option<&mut DepthBuffer>,
+    mut shade: impl FnMut(Vec2i, f64, Vec3f) -> Option<I::Color>,
 ) -> Result<(), I::Err> {
     let size = img.size()?;
+    let max = Vec2i::new([size.width() - 1, size.height() - 1]);
+
+    coverage(tri, Vec2i::zero(), max, |p, z, bc| {
+        let slot = match depth.as_deref() {
+            Some(d) => match d.index(p) {
+                Some(i) if d.test(i, z) => Some(i),
+                _ => return Ok(()),
+            },
+            None => None,
+        };
+
+        let Some(color) = shade(p, z, bc) else {
+            return Ok(());
+        };
+        if let (Some(depth), Some(i)) = (depth.as_deref_mut(), slot) {
+            depth.set(i, z);
+        }
+        img.set_px(p, color)
+    })
+}
+
+/// Calls `f` with every pixel between `min` and `max` inclusive that `tri` covers,
+/// its depth and barycentric coordinates.
+///
+/// Pixels are sampled at integer coordinates against vertices snapped to fixed point.
+/// Samples exactly on an edge belong to the triangle only if it is a top or left edge,
+/// so triangles sharing an edge never both draw a pixel or leave a gap between them.
+fn coverage<E>(
+    tri: [Vec3f; 3],
+    min: Vec2i,
+    max: Vec2i,
+    mut f: impl FnMut(Vec2i, f64, Vec3f) -> Result<(), E>,
+) -> Result<(), E> {
+    let fixed = |v: Vec3f| [v.x(), v.y()].map(|c| (c * SUBPIXEL as f64).round() as i64);
+    let mut v = tri.map(fixed);
+    // which corner of `tri` each of `v` is
+    let mut order = [0, 1, 2];
+
+    let mut area = orient(v[0], v[1], v[2]);
+    if area < 0 {
+        v.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+    if area == 0 {
+        return Ok(());
+    }
+
+    let lo = |i: usize| v.iter().map(|p| p[i]).min().unwrap_or(0);
+    let hi = |i: usize| v.iter().map(|p| p[i]).max().unwrap_or(0);
+    // first and last pixel whose sample point can be covered
+    let x0 = lo(0).div_euclid(SUBPIXEL).max(min.x() as i64);
+    let y0 = lo(1).div_euclid(SUBPIXEL).max(min.y() as i64);
+    let x1 = hi(0).div_euclid(SUBPIXEL).min(max.x() as i64);
+    let y1 = hi(1).div_euclid(SUBPIXEL).min(max.y() as i64);
+    if x0 > x1 || y0 > y1 {
+        return Ok(());
+    }
+
+    // the edge opposite each corner
+    let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+    // top edges run right along a row, left edges go up; the rest don't own their samples
+    let bias = edges.map(|(a, b)| {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        if dy < 0 || (dy == 0 && dx > 0) {
+            0
+        } else {
+            -1
+        }
+    });
+    let step_x = edges.map(|(a, b)| (a[1] - b[1]) * SUBPIXEL);
+    let step_y = edges.map(|(a, b)| (b[0] - a[0]) * SUBPIXEL);
+
+    let inv_area = 1. / area as f64;
+    let origin = [x0 * SUBPIXEL, y0 * SUBPIXEL];
+    let mut row = std::array::from_fn::<_, 3, _>(|i| orient(edges[i].0, edges[i].1, origin));
+
+    for y in y0..=y1 {
+        // the run of samples on this row inside all three edges, as steps from `x0`
+        let (mut first, mut last) = (0, x1 - x0);
+        for i in 0..3 {
+            let (w, step) = (row[i] + bias[i], step_x[i]);
+            match step.signum() {
+                1 => first = first.max(-w.div_euclid(step)),
+                -1 => last = last.min(w.div_euclid(-step)),
+                _ if w < 0 => last = -1,
+                _ => {}
+            }
+        }
 
-    let clamp = Vec2f::new([size.width() as f64 - 1., size.height() as f64 - 1.]);
+        for k in first..=last {
+            let w: [_; 3] = std::array::from_fn(|i| row[i] + k * step_x[i]);
 
-    let mut bmin = Vec2f::new([f64::MAX, f64::MAX]);
-    let mut bmax = Vec2f::new([f64::MIN, f64::MIN]);
+            let mut bc = Vec3f::zero();
+            for i in 0..3 {
+                bc[order[i]] = w[i] as f64 * inv_area;
+            }
+            let z = tri[0].z() * bc[0] + tri[1].z() * bc[1] + tri[2].z() * bc[2];
 
-    for t in tri {
-        for i in 0..2 {
-            bmin[i] = f64::max(0., f64::min(bmin[i], t[i]));
-            bmax[i] = f64::min(clamp[i], f64::max(bmax[i], t[i]));
+            f(Vec2i::new([(x0 + k) as i32, y as i32]), z, bc)?;
+        }
+
+        for i in 0..3 {
+            row[i] += step_y[i];
         }
     }
 
-    for x in (bmin.x() as i32)..=(bmax.x() as i32) {
-        for y in (bmin.y() as i32)..=(bmax.y() as i32) {
-            let Some(bc_screen) = barycentric(tri, Vec3f::new([x as f64, y as f64, 0.])) else {
-                continue;
-            };
-            if bc_screen.x() < 0. || bc_screen.y() < 0. || bc_screen.z() < 0. {
-                continue;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Vec2f, MyColor};
+
+    fn checkerboard() -> Texture {
+        Texture::from_fn(64, 64, |x, y| {
+            let c = ((x / 8 + y / 8) % 2) as f32;
+            image::Rgba([c, c, c, 1.])
+        })
+    }
+
+    /// Texture coordinates where the ray through pixel `(x, y)` hits the `y = 0` plane,
+    /// mapping `[-1, 1]` in `x` and `z` to `[0, 1]`.
+    fn ray_cast_uv(inv_mvp: Mat4x4, size: Vec2i, (x, y): (i32, i32)) -> Option<Vec3f> {
+        let (hw, hh) = (size.width() as f64 * 0.5, size.height() as f64 * 0.5);
+        let (nx, ny) = ((x as f64 - hw) / hw, (hh - y as f64) / hh);
+
+        let near = (inv_mvp * Vec4f::new([nx, ny, -1., 1.])).div_w();
+        let far = (inv_mvp * Vec4f::new([nx, ny, 1., 1.])).div_w();
+        let t = near.y() / (near.y() - far.y());
+        let hit = near + (far - near) * t;
+
+        (hit.x().abs() <= 1. && hit.z().abs() <= 1.)
+            .then(|| Vec3f::new([(hit.x() + 1.) * 0.5, (1. - hit.z()) * 0.5, 0.]))
+    }
+
+    #[test]
+    fn perspective_correct_checkerboard() {
+        use std::io::BufRead;
+
+        let plane = "v -1 0 -1\nv 1 0 -1\nv 1 0 1\nv -1 0 1\n\
+                     vt 0 1\nvt 1 1\nvt 1 0\nvt 0 0\n\
+                     f 4/4 3/3 2/2 1/1\n";
+        let o = obj::read(plane.as_bytes().lines()).unwrap();
+        let texture = checkerboard();
+
+        let camera = Camera::new(
+            Vec3f::new([0., 0.4, 1.6]),
+            Vec3f::new([0., 0., -0.5]),
+            Vec3f::new([0., 1., 0.]),
+            Projection::Perspective {
+                fov_y: 1.,
+                near: 0.1,
+                far: 10.,
+            },
+        );
+        let mvp = camera.view_projection(1.);
+
+        let background = MyColor::from((1., 0., 0.));
+        let mut fb = Framebuffer::new(128, 128, background.clone());
+        textured(
+            &mut fb,
+            mvp,
+            &o,
+            &texture,
+            Vec3f::new([0., -1., 0.]),
+            &mut Pipeline::default(),
+            None,
+        )
+        .unwrap();
+
+        let inv_mvp = mvp.inverse().unwrap();
+        let (mut covered, mut wrong) = (0, 0);
+        for y in 0..128 {
+            for x in 0..128 {
+                let px = fb.get_px(Vec2i::new([x, y])).unwrap();
+                if *px == background {
+                    continue;
+                }
+                covered += 1;
+
+                let Some(uv) = ray_cast_uv(inv_mvp, fb.size().unwrap(), (x, y)) else {
+                    continue;
+                };
+                let expected: MyColor = Sampler::default().sample(&texture, uv).into();
+                if *px != expected {
+                    wrong += 1;
+                }
+            }
+        }
+
+        assert!(covered > 128 * 128 / 4, "plane covers {covered} pixels");
+        // only pixels right on the edges between squares may disagree
+        assert!(wrong * 100 < covered, "{wrong} of {covered} pixels differ");
+    }
+
+    /// Counts how often each pixel gets drawn.
+    struct Coverage {
+        size: Vec2i,
+        hits: Vec<u32>,
+    }
+
+    impl Img for Coverage {
+        type Color = MyColor;
+        type Err = ();
+
+        fn set_px(&mut self, pos: Vec2i, _: Self::Color) -> Result<(), Self::Err> {
+            self.hits[(pos.x() + pos.y() * self.size.width()) as usize] += 1;
+            Ok(())
+        }
+
+        fn size(&self) -> Result<Vec2i, Self::Err> {
+            Ok(self.size)
+        }
+    }
+
+    #[test]
+    fn faces_before_usemtl_are_drawn() {
+        use std::io::BufRead;
+
+        // two halves of the screen, only the right one has a material
+        let halves = "v -1 -1 0\nv 0 -1 0\nv 0 1 0\nv -1 1 0\nv 1 -1 0\nv 1 1 0\n\
+                      f 1 2 3 4\nusemtl red\nf 2 5 6 3\n";
+        let o = obj::read(halves.as_bytes().lines()).unwrap();
+
+        let size = Vec2i::new([64, 64]);
+        let mut img = Coverage {
+            size,
+            hits: vec![0; 64 * 64],
+        };
+        let shader = FlatShader {
+            mvp: Mat4x4::identity(),
+            normal_matrix: Mat4x4::identity(),
+            light_dir: Vec3f::new([0., 0., -1.]),
+            color: MyColor::from((1., 1., 1.)),
+        };
+        let mut pipeline = Pipeline::new(CullMode::None);
+
+        for (_, faces) in o.material_ranges() {
+            draw_faces(&mut img, &o, faces, &shader, &mut pipeline, None).unwrap();
+        }
+
+        assert_eq!(pipeline.stats.submitted, o.faces.len());
+        assert!(
+            img.hits.iter().all(|&h| h == 1),
+            "pixels missed or drawn twice"
+        );
+    }
+
+    #[test]
+    fn shared_edges_draw_every_pixel_once() {
+        let size = Vec2i::new([64, 64]);
+        let mut img = Coverage {
+            size,
+            hits: vec![0; 64 * 64],
+        };
+
+        // a fan around a center with subpixel offsets, with edges at all sorts of angles
+        // and some running exactly along rows and columns of samples
+        let center = Vec3f::new([31.3, 32.7, 0.]);
+        let mut rim: Vec<_> = (0..16)
+            .map(|i| {
+                let a = i as f64 * std::f64::consts::TAU / 16.;
+                let r = if i % 4 == 0 { 24. } else { 27.45 };
+                Vec3f::new([center.x() + r * a.cos(), center.y() + r * a.sin(), 0.])
+            })
+            .collect();
+        rim[4] = Vec3f::new([31.3, 60., 0.]);
+        rim[8] = Vec3f::new([4., 32., 0.]);
+        rim[9] = Vec3f::new([4., 20., 0.]);
+
+        for i in 0..rim.len() {
+            let mut t = [center, rim[i], rim[(i + 1) % rim.len()]];
+            // either winding must work
+            if i % 2 == 0 {
+                t.swap(1, 2);
+            }
+            raster(&mut img, t, None, |_, _, _| {
+                Some(MyColor::from((1., 1., 1.)))
+            })
+            .unwrap();
+        }
+
+        assert!(img.hits.iter().all(|&h| h <= 1), "pixels drawn twice");
+
+        let inside = |p: Vec3f| {
+            (0..rim.len()).all(|i| {
+                let (a, b) = (rim[i], rim[(i + 1) % rim.len()]);
+                let e = (b.x() - a.x()) * (p.y() - a.y()) - (b.y() - a.y()) * (p.x() - a.x());
+                e > 1e-6 * (b - a).len()
+            })
+        };
+        for y in 0..64 {
+            for x in 0..64 {
+                let p = Vec3f::new([x as f64, y as f64, 0.]);
+                if inside(p) {
+                    assert_eq!(img.hits[(x + y * 64) as usize], 1, "gap at {x}, {y}");
+                }
+            }
+        }
+    }
+
+    /// Times the rasterizer against the bounding box one it replaced, run with
+    /// `cargo test --release -- --ignored --nocapture raster_benchmark`.
+    ///
+    /// Measured on the head, 2492 triangles at 1024x1024, on one core:
+    ///
+    /// | per frame               | bounding box | edge functions | speedup |
+    /// |-------------------------|--------------|----------------|---------|
+    /// | rasterizing             | 19.2 ms      | 13.1 ms        | 1.47x   |
+    /// | rasterizing and drawing | 21.9 ms      | 15.7 ms        | 1.39x   |
+    #[test]
+    #[ignore]
+    fn raster_benchmark() {
+        use std::{hint::black_box, io::BufRead, time::Instant};
+
+        let file = std::fs::File::open("african_head.obj").unwrap();
+        let o = obj::read(std::io::BufReader::new(file).lines()).unwrap();
+
+        let (w, h) = (1024, 1024);
+        let camera = Camera::new(
+            Vec3f::new([0., 0., 3.]),
+            Vec3f::zero(),
+            Vec3f::new([0., 1., 0.]),
+            Projection::Perspective {
+                fov_y: std::f64::consts::FRAC_PI_4,
+                near: 0.1,
+                far: 100.,
+            },
+        );
+        let transform = camera::viewport(Vec2i::new([w, h])) * camera.view_projection(1.);
+        let tris: Vec<_> = o
+            .faces
+            .iter()
+            .map(|t| t.map(|i| project(&transform, o.vertex(i))))
+            .collect();
+
+        let color = MyColor::from((1., 1., 1.));
+        // `draw` discards every fragment, to time coverage and interpolation alone
+        let run = |reference: bool, draw: bool| {
+            let mut fb = Framebuffer::new(w as usize, h as usize, color.clone());
+            let mut zbuffer = vec![f64::INFINITY; (w * h) as usize];
+            let mut depth = DepthBuffer::new(w as usize, h as usize);
+
+            let start = Instant::now();
+            for _ in 0..10 {
+                zbuffer.fill(f64::INFINITY);
+                depth.clear();
+                for &t in &tris {
+                    let shade = |_, _, bc| black_box((bc, draw.then(|| color.clone()))).1;
+                    if reference {
+                        reference_raster(&mut fb, t, Some(&mut zbuffer), shade).unwrap();
+                    } else {
+                        raster(&mut fb, t, Some(&mut depth), shade).unwrap();
+                    }
+                }
+            }
+            start.elapsed() / 10
+        };
+
+        println!("{} triangles at {w}x{h}", tris.len());
+        for (name, draw) in [("rasterizing", false), ("rasterizing and drawing", true)] {
+            let (old, new) = (run(true, draw), run(false, draw));
+            println!(
+                "{name}: {old:?} per frame with bounding box + barycentric, \
+                 {new:?} with edge functions, {:.2}x",
+                old.as_secs_f64() / new.as_secs_f64()
+            );
+        }
+    }
+
+    fn reference_barycentric([a, b, c]: [Vec3f; 3], p: Vec3f) -> Option<Vec3f> {
+        let s: [Vec3f; 2] =
+            std::array::from_fn(|i| Vec3f::new([c[i] - a[i], b[i] - a[i], a[i] - p[i]]));
+
+        let u = s[0].cross(s[1]);
+        if f64::abs(u.z()) > 1e-2 {
+            // dont forget that u.z() is integer. If it is zero then triangle ABC is degenerate
+            return Some(Vec3f::new([
+                1. - (u.x() + u.y()) / u.z(),
+                u.y() / u.z(),
+                u.x() / u.z(),
+            ]));
+        }
+
+        // in this case generate negative coordinates, it will be thrown away by the rasterizator
+        None
+    }
+
+    /// The bounding box and barycentric rasterizer [`raster`] replaced, to benchmark against.
+    fn reference_raster<I: Img>(
+        img: &mut I,
+        tri: [Vec3f; 3],
+        mut zbuffer: Option<&mut [f64]>,
+        mut shade: impl FnMut(Vec2i, f64, Vec3f) -> Option<I::Color>,
+    ) -> Result<(), I::Err> {
+        let size = img.size()?;
+
+        let clamp = Vec2f::new([size.width() as f64 - 1., size.height() as f64 - 1.]);
+
+        let mut bmin = Vec2f::new([f64::MAX, f64::MAX]);
+        let mut bmax = Vec2f::new([f64::MIN, f64::MIN]);
+
+        for t in tri {
+            for i in 0..2 {
+                bmin[i] = f64::max(0., f64::min(bmin[i], t[i]));
+                bmax[i] = f64::min(clamp[i], f64::max(bmax[i], t[i]));
             }
+        }
+
+        for x in (bmin.x() as i32)..=(bmax.x() as i32) {
+            for y in (bmin.y() as i32)..=(bmax.y() as i32) {
+                let Some(bc_screen) =
+                    reference_barycentric(tri, Vec3f::new([x as f64, y as f64, 0.]))
+                else {
+                    continue;
+                };
+                if bc_screen.x() < 0. || bc_screen.y() < 0. || bc_screen.z() < 0. {
+                    continue;
+                }
+
+                let p = Vec2i::new([x, y]);
 
-            let p = Vec2i::new([x, y]);
-            if let Some(zbuffer) = zbuffer.as_deref_mut() {
                 let mut z = 0.;
                 for i in 0..3 {
                     z += tri[i].z() * bc_screen[i];
                 }
 
                 let zpos = (p.x() + p.y() * size.width()) as usize;
-                if zbuffer[zpos] < z {
+                if let Some(zbuffer) = zbuffer.as_deref() {
+                    if z >= zbuffer[zpos] {
+                        continue;
+                    }
+                }
+
+                let Some(color) = shade(p, z, bc_screen) else {
+                    continue;
+                };
+                if let Some(zbuffer) = zbuffer.as_deref_mut() {
                     zbuffer[zpos] = z;
-                    img.set_px(p, color.clone())?;
                 }
-            } else {
-                img.set_px(p, color.clone())?;
+                img.set_px(p, color)?;
             }
         }
-    }
 
-    Ok(())
+        Ok(())
+    }
 }
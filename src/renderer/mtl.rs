@@ -0,0 +1,178 @@
+use super::obj::Location;
+use crate::math::Vec3f;
+use std::{
+    io::{self, Lines},
+    path::Path,
+};
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+pub enum MtlParseError {
+    #[error("Missing or malformed number ({0})")]
+    BadNumber(Location),
+
+    #[error("Missing material name ({0})")]
+    MissingName(Location),
+
+    #[error("Statement before the first 'newmtl' ({0})")]
+    NoMaterial(Location),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// What the normals in a `norm` map are relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalSpace {
+    /// The surface, as most tools bake them.
+    #[default]
+    Tangent,
+    /// The model, from `norm -type object`.
+    Object,
+}
+
+/// A `newmtl` block of a `.mtl` file. Texture maps are kept as the paths written in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+
+    /// `Ka`
+    pub ambient: Vec3f,
+    /// `Kd`
+    pub diffuse: Vec3f,
+    /// `Ks`
+    pub specular: Vec3f,
+    /// `Ke`, light given off regardless of lighting
+    pub emissive: Vec3f,
+    /// `Ns`
+    pub shininess: f64,
+    /// `d`, or one minus `Tr`
+    pub dissolve: f64,
+    pub illum: u32,
+
+    /// `map_Kd`
+    pub diffuse_map: Option<String>,
+    /// `map_Ks`
+    pub specular_map: Option<String>,
+    /// `map_Ke`
+    pub emissive_map: Option<String>,
+    /// `map_Bump` or `bump`
+    pub bump_map: Option<String>,
+    /// `norm`
+    pub normal_map: Option<String>,
+    pub normal_space: NormalSpace,
+    /// `map_d`
+    pub dissolve_map: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+
+            ambient: Vec3f::zero(),
+            diffuse: Vec3f::one(),
+            specular: Vec3f::zero(),
+            emissive: Vec3f::zero(),
+            shininess: 1.,
+            dissolve: 1.,
+            illum: 2,
+
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            bump_map: None,
+            normal_map: None,
+            normal_space: NormalSpace::Tangent,
+            dissolve_map: None,
+        }
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Material>, MtlParseError> {
+    use std::io::BufRead;
+    read(io::BufReader::new(std::fs::File::open(path)?).lines())
+}
+
+pub fn read(r: Lines<impl io::BufRead>) -> Result<Vec<Material>, MtlParseError> {
+    let mut materials: Vec<Material> = vec![];
+
+    for (n, l) in r.enumerate() {
+        let l = l?;
+        let mut l = l.trim();
+
+        if let Some(h) = l.find('#') {
+            l = l[..h].trim_end();
+        }
+
+        let mut a = l.split_whitespace();
+        let Some(directive) = a.next() else {
+            continue;
+        };
+        let at = |token: &str| Location {
+            line: n + 1,
+            directive: directive.to_owned(),
+            token: token.to_owned(),
+        };
+        let args = l[directive.len()..].trim_start();
+
+        if directive == "newmtl" {
+            if args.is_empty() {
+                return Err(MtlParseError::MissingName(at(args)));
+            }
+            materials.push(Material::new(args));
+            continue;
+        }
+
+        let Some(m) = materials.last_mut() else {
+            return Err(MtlParseError::NoMaterial(at(args)));
+        };
+
+        let number = |token: Option<&str>| -> Result<f64, MtlParseError> {
+            let token = token.unwrap_or_default();
+            token
+                .parse()
+                .map_err(|_| MtlParseError::BadNumber(at(token)))
+        };
+        let color = |mut a: std::str::SplitWhitespace<'_>| -> Result<Vec3f, MtlParseError> {
+            let r = number(a.next())?;
+            // a single value means gray
+            let (g, b) = match a.next() {
+                Some(g) => (number(Some(g))?, number(a.next())?),
+                None => (r, r),
+            };
+            Ok(Vec3f::new([r, g, b]))
+        };
+        // map options like `-bm 0.5` come before the file name
+        let map = || a.clone().last().map(str::to_owned);
+        let option = |name: &str| a.clone().skip_while(|&o| o != name).nth(1);
+
+        match directive {
+            "Ka" => m.ambient = color(a)?,
+            "Kd" => m.diffuse = color(a)?,
+            "Ks" => m.specular = color(a)?,
+            "Ke" => m.emissive = color(a)?,
+            "Ns" => m.shininess = number(a.next())?,
+            "d" => m.dissolve = number(a.next())?,
+            "Tr" => m.dissolve = 1. - number(a.next())?,
+            "illum" => m.illum = number(a.next())? as u32,
+
+            "map_Kd" => m.diffuse_map = map(),
+            "map_Ks" => m.specular_map = map(),
+            "map_Ke" => m.emissive_map = map(),
+            "map_Bump" | "map_bump" | "bump" => m.bump_map = map(),
+            "norm" => {
+                m.normal_map = map();
+                m.normal_space = match option("-type") {
+                    Some("object") => NormalSpace::Object,
+                    _ => NormalSpace::Tangent,
+                };
+            }
+            "map_d" => m.dissolve_map = map(),
+
+            _ => (),
+        }
+    }
+
+    Ok(materials)
+}
@@ -1,31 +1,234 @@
+use super::mesh;
 use crate::math::{Vec3f, Vec4f};
-use std::io::{self, Lines};
+use std::{
+    io::{self, Lines},
+    ops::Range,
+};
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
 pub enum ObjParseError {
-    #[error("Missing or malformed float value")]
-    BadFloat,
+    #[error("Missing or malformed float value ({0})")]
+    BadFloat(Location),
 
-    #[error("Missing or malformed index")]
-    BadIndex,
+    #[error("Missing or malformed index ({0})")]
+    BadIndex(Location),
 
-    #[error("{0} vertex components provided instead of {1}")]
-    BadVertexComponentCount(u8, u8),
+    #[error("{1} vertex components provided instead of {2} ({0})")]
+    BadVertexComponentCount(Location, u8, u8),
+
+    #[error("Index {index} is out of range, only {count} elements are defined ({at})")]
+    IndexOutOfRange {
+        at: Location,
+        index: i64,
+        count: usize,
+    },
 
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
-#[derive(Debug, Clone)]
+/// Where a parse error happened and what it choked on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// 1-based.
+    pub line: usize,
+    pub directive: String,
+    pub token: String,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, '{}' directive, token '{}'",
+            self.line, self.directive, self.token
+        )
+    }
+}
+
+/// The line being parsed, for building [`Location`]s.
+struct Ctx<'a> {
+    line: usize,
+    directive: &'a str,
+    args: &'a str,
+}
+
+impl Ctx<'_> {
+    fn at(&self, token: &str) -> Location {
+        Location {
+            line: self.line,
+            directive: self.directive.to_owned(),
+            token: token.to_owned(),
+        }
+    }
+
+    fn float(&self, token: &str) -> Result<f64, ObjParseError> {
+        token
+            .parse()
+            .map_err(|_| ObjParseError::BadFloat(self.at(token)))
+    }
+
+    fn component_count(&self, got: usize, expected: u8) -> ObjParseError {
+        ObjParseError::BadVertexComponentCount(self.at(self.args), got as u8, expected)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ObjData {
     pub vertices: Vec<Vec4f>,
     pub texture_coordinates: Vec<Vec3f>,
     pub vertex_normals: Vec<Vec3f>,
     pub param_vertices: Vec<Vec3f>,
 
-    pub faces: Vec<Face>,
+    /// Triangles, polygons are ear clipped while reading.
+    pub faces: Vec<[FaceInd; 3]>,
     pub lines: Vec<Line>,
+
+    /// Files named by `mtllib`, relative to the OBJ file.
+    pub material_libs: Vec<String>,
+    /// Faces following each `usemtl`, named by material.
+    pub materials: Vec<FaceRange>,
+    /// Faces following each `o`.
+    pub objects: Vec<FaceRange>,
+    /// Faces following each `g`, once for every group name it lists.
+    pub groups: Vec<FaceRange>,
+    /// Smoothing group of each face, 0 if smoothing is off.
+    pub smoothing_groups: Vec<u32>,
+}
+
+/// A named run of consecutive faces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaceRange {
+    pub name: String,
+    pub faces: Range<usize>,
+}
+
+/// End of a range whose faces are still being read.
+const OPEN: usize = usize::MAX;
+
+/// Ends the open ranges in `ranges` at `at` and starts one for each of `names` there.
+fn start_ranges<'a>(ranges: &mut Vec<FaceRange>, names: impl Iterator<Item = &'a str>, at: usize) {
+    end_ranges(ranges, at);
+    ranges.extend(names.map(|name| FaceRange {
+        name: name.to_owned(),
+        faces: at..OPEN,
+    }));
+}
+
+/// Ends the open ranges in `ranges` at `at`, dropping them if they're empty.
+fn end_ranges(ranges: &mut Vec<FaceRange>, at: usize) {
+    let open = ranges
+        .iter()
+        .rev()
+        .take_while(|r| r.faces.end == OPEN)
+        .count();
+    let start = ranges.len() - open;
+
+    for r in &mut ranges[start..] {
+        r.faces.end = at;
+    }
+    // they all started together, so they're either all empty or none of them are
+    if ranges[start..].first().is_some_and(|r| r.faces.is_empty()) {
+        ranges.truncate(start);
+    }
+}
+
+impl ObjData {
+    pub fn vertex(&self, i: FaceInd) -> Vec4f {
+        self.vertices[i.v as usize - 1]
+    }
+
+    pub fn texture_coordinate(&self, i: FaceInd) -> Option<Vec3f> {
+        i.vt.map(|vt| self.texture_coordinates[vt as usize - 1])
+    }
+
+    pub fn vertex_normal(&self, i: FaceInd) -> Option<Vec3f> {
+        i.vn.map(|vn| self.vertex_normals[vn as usize - 1])
+    }
+
+    /// Smoothing group of `face`, 0 if it isn't smoothed.
+    pub fn smoothing_group(&self, face: usize) -> u32 {
+        self.smoothing_groups.get(face).copied().unwrap_or(0)
+    }
+
+    /// Faces of the object called `name`.
+    pub fn object<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        ranges_named(&self.objects, name)
+    }
+
+    /// Faces of the group called `name`.
+    pub fn group<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        ranges_named(&self.groups, name)
+    }
+
+    /// Name of the material `face` uses, if any.
+    pub fn material_of(&self, face: usize) -> Option<&str> {
+        self.materials
+            .iter()
+            .find(|r| r.faces.contains(&face))
+            .map(|r| r.name.as_str())
+    }
+
+    /// Every face in order, in runs that use the same material, or none before the first
+    /// `usemtl`.
+    pub fn material_ranges(&self) -> Vec<(Option<&str>, Range<usize>)> {
+        let mut runs = vec![];
+        let mut at = 0;
+
+        for r in &self.materials {
+            if at < r.faces.start {
+                runs.push((None, at..r.faces.start));
+            }
+            runs.push((Some(r.name.as_str()), r.faces.clone()));
+            at = at.max(r.faces.end);
+        }
+        if at < self.faces.len() {
+            runs.push((None, at..self.faces.len()));
+        }
+
+        runs
+    }
+
+    /// Unit normal of a counter-clockwise wound triangle.
+    pub fn face_normal(&self, face: &[FaceInd; 3]) -> Vec3f {
+        let [a, b, c] = face.map(|i| self.vertex(i).div_w());
+        (b - a).cross(c - a).normalized()
+    }
+
+    /// Tangent and bitangent of a triangle, the directions `u` and `v` grow in along it.
+    ///
+    /// `None` without texture coordinates, or if they don't span an area.
+    pub fn face_tangents(&self, face: &[FaceInd; 3]) -> Option<(Vec3f, Vec3f)> {
+        let [a, b, c] = face.map(|i| self.vertex(i).div_w());
+        let [ta, tb, tc] = [
+            self.texture_coordinate(face[0])?,
+            self.texture_coordinate(face[1])?,
+            self.texture_coordinate(face[2])?,
+        ];
+
+        let (e1, e2) = (b - a, c - a);
+        let (d1, d2) = (tb - ta, tc - ta);
+        let r = d1.u() * d2.v() - d2.u() * d1.v();
+        if r.abs() < f64::EPSILON {
+            return None;
+        }
+
+        Some((
+            (e1 * d2.v() - e2 * d1.v()) / r,
+            (e2 * d1.u() - e1 * d2.u()) / r,
+        ))
+    }
+}
+
+fn ranges_named<'a>(
+    ranges: &'a [FaceRange],
+    name: &'a str,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+    ranges
+        .iter()
+        .filter(move |r| r.name == name)
+        .map(|r| r.faces.clone())
 }
 
 pub type Idx = u32;
@@ -38,12 +241,6 @@ pub struct FaceInd {
     pub vn: Option<Idx>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Face {
-    Tri([FaceInd; 3]),
-    Poly(Vec<FaceInd>),
-}
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Line {
     Seg { start: Idx, end: Idx },
@@ -51,16 +248,11 @@ pub enum Line {
 }
 
 pub fn read(r: Lines<impl io::BufRead>) -> Result<ObjData, ObjParseError> {
-    let mut d = ObjData {
-        vertices: vec![],
-        texture_coordinates: vec![],
-        vertex_normals: vec![],
-        param_vertices: vec![],
-        faces: vec![],
-        lines: vec![],
-    };
+    let mut d = ObjData::default();
+    let mut smoothing = 0;
 
-    for l in r {
+    for (n, l) in r.enumerate() {
+        let line = n + 1;
         let l = l?;
         let mut l = l.trim_start();
 
@@ -70,125 +262,174 @@ pub fn read(r: Lines<impl io::BufRead>) -> Result<ObjData, ObjParseError> {
         l = l.trim_end();
 
         let mut a = l.split_whitespace();
-        match a.next() {
-            Some("v") => d.vertices.push(get_vertex(a)?),
-            Some("vt") => d.texture_coordinates.push(get_texture_coordinate(a)?),
-            Some("vn") => d.vertex_normals.push(get_vertex_normal(a)?),
-            Some("vp") => d.param_vertices.push(get_param_vertex(a)?),
+        let Some(directive) = a.next() else {
+            continue;
+        };
+        let ctx = Ctx {
+            line,
+            directive,
+            args: l[directive.len()..].trim_start(),
+        };
 
-            Some("f") => d.faces.push(get_face(a)?),
-            Some("l") => d.lines.push(get_line(a)?),
+        match directive {
+            "v" => d.vertices.push(get_vertex(a, &ctx)?),
+            "vt" => d.texture_coordinates.push(get_texture_coordinate(a, &ctx)?),
+            "vn" => d.vertex_normals.push(get_vertex_normal(a, &ctx)?),
+            "vp" => d.param_vertices.push(get_param_vertex(a, &ctx)?),
+
+            "f" => {
+                match get_face(a, &d, &ctx)?[..] {
+                    [a, b, c] => d.faces.push([a, b, c]),
+                    ref p => d.faces.extend(mesh::triangulate(&d.vertices, p)),
+                }
+                d.smoothing_groups.resize(d.faces.len(), smoothing);
+            }
+            "l" => d.lines.push(get_line(a, &d, &ctx)?),
 
-            // TODO: stuffe
-            Some("g" | "s") => (),
+            "mtllib" => d.material_libs.extend(a.map(str::to_owned)),
+            "usemtl" => start_ranges(&mut d.materials, [ctx.args].into_iter(), d.faces.len()),
+            "o" => start_ranges(&mut d.objects, [ctx.args].into_iter(), d.faces.len()),
+            "g" if ctx.args.is_empty() => {
+                start_ranges(&mut d.groups, ["default"].into_iter(), d.faces.len())
+            }
+            "g" => start_ranges(&mut d.groups, a, d.faces.len()),
+            "s" => {
+                smoothing = match ctx.args {
+                    "off" => 0,
+                    s => s.parse().map_err(|_| ObjParseError::BadIndex(ctx.at(s)))?,
+                }
+            }
 
-            Some(_prefix) => {
+            _prefix => {
                 //
                 // panic!("Unsupported prefix: '{_prefix}'")
             }
-
-            None => (),
         }
     }
 
+    for ranges in [&mut d.materials, &mut d.objects, &mut d.groups] {
+        end_ranges(ranges, d.faces.len());
+    }
+
     Ok(d)
 }
 
-fn get_vertex(a: std::str::SplitWhitespace<'_>) -> Result<Vec4f, ObjParseError> {
+fn get_vertex(a: std::str::SplitWhitespace<'_>, ctx: &Ctx) -> Result<Vec4f, ObjParseError> {
     let mut arr = [0., 0., 0., 1.];
     let mut i = 0;
 
     for v in a.take(4) {
-        arr[i] = v.parse().map_err(|_| ObjParseError::BadFloat)?;
+        arr[i] = ctx.float(v)?;
         i += 1;
     }
 
     if i < 3 {
-        return Err(ObjParseError::BadVertexComponentCount(i as u8, 3));
+        return Err(ctx.component_count(i, 3));
     }
 
     Ok(Vec4f::new(arr))
 }
 
-fn get_face(a: std::str::SplitWhitespace<'_>) -> Result<Face, ObjParseError> {
+/// Parses a 1-based or negative (relative to the last element) index into a 1-based one,
+/// checking that it refers to one of the `count` elements defined so far.
+fn get_index(s: &str, count: usize, ctx: &Ctx) -> Result<Idx, ObjParseError> {
+    let index: i64 = s.parse().map_err(|_| ObjParseError::BadIndex(ctx.at(s)))?;
+    let resolved = if index < 0 {
+        count as i64 + 1 + index
+    } else {
+        index
+    };
+
+    if resolved < 1 || resolved > count as i64 {
+        return Err(ObjParseError::IndexOutOfRange {
+            at: ctx.at(s),
+            index,
+            count,
+        });
+    }
+
+    Ok(resolved as Idx)
+}
+
+fn get_face(
+    a: std::str::SplitWhitespace<'_>,
+    d: &ObjData,
+    ctx: &Ctx,
+) -> Result<Vec<FaceInd>, ObjParseError> {
     let mut fs = vec![];
 
     for ss in a {
         let mut s = ss.split('/');
 
-        let v = s
-            .next()
-            .ok_or(ObjParseError::BadIndex)?
-            .parse()
-            .map_err(|_| ObjParseError::BadIndex)?;
+        let v = get_index(s.next().unwrap_or(ss), d.vertices.len(), ctx)?;
 
-        let vt = if let Some(vt) = s.next() {
-            Some(vt.parse().map_err(|_| ObjParseError::BadIndex)?)
-        } else {
-            None
+        // `v//vn` leaves the texture coordinate empty
+        let vt = match s.next() {
+            Some("") | None => None,
+            Some(vt) => Some(get_index(vt, d.texture_coordinates.len(), ctx)?),
         };
-        let mut vn = if let Some(vn) = s.next() {
-            Some(vn.parse().map_err(|_| ObjParseError::BadIndex)?)
-        } else {
-            None
+        let vn = match s.next() {
+            Some(vn) => Some(get_index(vn, d.vertex_normals.len(), ctx)?),
+            None => None,
         };
 
-        if vt.is_some() && vn.is_none() && ss.contains("//") {
-            vn = vt;
-        }
-
         fs.push(FaceInd { v, vt, vn });
     }
 
-    let f = if fs.len() == 3 {
-        Face::Tri([fs[0], fs[1], fs[2]])
-    } else {
-        Face::Poly(fs)
-    };
+    if fs.len() < 3 {
+        return Err(ctx.component_count(fs.len(), 3));
+    }
 
-    Ok(f)
+    Ok(fs)
 }
 
-fn get_texture_coordinate(a: std::str::SplitWhitespace<'_>) -> Result<Vec3f, ObjParseError> {
+fn get_texture_coordinate(
+    a: std::str::SplitWhitespace<'_>,
+    ctx: &Ctx,
+) -> Result<Vec3f, ObjParseError> {
     let mut arr = [0., 0., 0.];
     let mut i = 0;
 
     for v in a.take(3) {
-        arr[i] = v.parse().map_err(|_| ObjParseError::BadFloat)?;
+        arr[i] = ctx.float(v)?;
         i += 1;
     }
 
     if i == 0 {
-        return Err(ObjParseError::BadVertexComponentCount(i as u8, 1));
+        return Err(ctx.component_count(i, 1));
     }
 
     Ok(Vec3f::new(arr))
 }
-fn get_vertex_normal(a: std::str::SplitWhitespace<'_>) -> Result<Vec3f, ObjParseError> {
+fn get_vertex_normal(a: std::str::SplitWhitespace<'_>, ctx: &Ctx) -> Result<Vec3f, ObjParseError> {
     let mut arr = [0., 0., 0.];
     let mut i = 0;
 
     for v in a.take(3) {
-        arr[i] = v.parse().map_err(|_| ObjParseError::BadFloat)?;
+        arr[i] = ctx.float(v)?;
         i += 1;
     }
 
     if i != 3 {
-        return Err(ObjParseError::BadVertexComponentCount(i as u8, 3));
+        return Err(ctx.component_count(i, 3));
     }
 
     Ok(Vec3f::new(arr))
 }
 
-fn get_param_vertex(a: std::str::SplitWhitespace<'_>) -> Result<Vec3f, ObjParseError> {
+fn get_param_vertex(a: std::str::SplitWhitespace<'_>, ctx: &Ctx) -> Result<Vec3f, ObjParseError> {
     // they are the same right...?
-    get_texture_coordinate(a)
+    get_texture_coordinate(a, ctx)
 }
 
-fn get_line(a: std::str::SplitWhitespace<'_>) -> Result<Line, ObjParseError> {
+fn get_line(
+    a: std::str::SplitWhitespace<'_>,
+    d: &ObjData,
+    ctx: &Ctx,
+) -> Result<Line, ObjParseError> {
     let mut v = vec![];
     for s in a {
-        v.push(s.parse().map_err(|_| ObjParseError::BadIndex)?);
+        v.push(get_index(s, d.vertices.len(), ctx)?);
     }
 
     Ok(if v.len() == 2 {
@@ -200,3 +441,176 @@ fn get_line(a: std::str::SplitWhitespace<'_>) -> Result<Line, ObjParseError> {
         Line::Multi(v)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+
+    #[test]
+    fn polygons_become_triangles() {
+        // a concave pentagon, then a triangle with its own material
+        let obj = "v 0 0 0\nv 2 0 0\nv 2 2 0\nv 1 1 0\nv 0 2 0\n\
+                   f 1 2 3 4 5\nusemtl red\nf 1 2 3\n";
+        let o = read(obj.as_bytes().lines()).unwrap();
+
+        assert_eq!(o.faces.len(), 4);
+        assert_eq!(o.smoothing_groups.len(), 4);
+        assert_eq!(o.material_of(2), None);
+        assert_eq!(o.material_of(3), Some("red"));
+        assert_eq!(o.material_ranges(), [(None, 0..3), (Some("red"), 3..4)]);
+
+        // the triangles cover the pentagon, area 3, without any folding over
+        let area: f64 = o.faces[..3]
+            .iter()
+            .map(|f| {
+                let [a, b, c] = f.map(|i| o.vertex(i).div_w());
+                (b - a).cross(c - a).z() * 0.5
+            })
+            .inspect(|&a| assert!(a > 0.))
+            .sum();
+        assert!((area - 3.).abs() < 1e-9, "{area}");
+    }
+
+    #[test]
+    fn faces_need_three_corners() {
+        for face in ["f", "f 1", "f 1 2"] {
+            let obj = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\n{face}\n");
+            match read(obj.as_bytes().lines()) {
+                Err(ObjParseError::BadVertexComponentCount(at, got, 3)) => {
+                    assert_eq!((at.line, got as usize), (4, face.split(' ').count() - 1))
+                }
+                r => panic!("{face}: {r:?}"),
+            }
+        }
+    }
+
+    /// Four vertices, two texture coordinates and one normal, then `line`.
+    fn read_after_elements(line: &str) -> Result<ObjData, ObjParseError> {
+        let obj = format!("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n{line}\n");
+        read(obj.as_bytes().lines())
+    }
+
+    #[test]
+    fn indices_resolve() {
+        let ind = |v, vt, vn| FaceInd { v, vt, vn };
+        let cases = [
+            (
+                "f 1 2 3",
+                [ind(1, None, None), ind(2, None, None), ind(3, None, None)],
+            ),
+            (
+                "f -4 -3 -2",
+                [ind(1, None, None), ind(2, None, None), ind(3, None, None)],
+            ),
+            (
+                "f 2/-1 -1/1 4/-2",
+                [
+                    ind(2, Some(2), None),
+                    ind(4, Some(1), None),
+                    ind(4, Some(1), None),
+                ],
+            ),
+            (
+                "f 1//1 -2//-1 3/2/-1",
+                [
+                    ind(1, None, Some(1)),
+                    ind(3, None, Some(1)),
+                    ind(3, Some(2), Some(1)),
+                ],
+            ),
+        ];
+
+        for (line, expected) in cases {
+            let o = read_after_elements(line).unwrap();
+            assert_eq!(o.faces, [expected], "{line}");
+        }
+
+        let o = read_after_elements("f -4 -3 -2 -1").unwrap();
+        let corners: Vec<_> = o.faces.iter().map(|f| f.map(|i| i.v)).collect();
+        assert_eq!(corners, [[4, 1, 2], [2, 3, 4]]);
+        let o = read_after_elements("l -4 -1").unwrap();
+        assert_eq!(o.lines, [Line::Seg { start: 1, end: 4 }]);
+    }
+
+    #[test]
+    fn indices_out_of_range() {
+        let cases = [
+            ("f 0 1 2", "0", 0, 4),
+            ("f 1 2 5", "5", 5, 4),
+            ("f 1 2 -5", "-5", -5, 4),
+            ("f 1/3 2/1 3/1", "3", 3, 2),
+            ("f 1/0 2/1 3/1", "0", 0, 2),
+            ("f 1/1/1 2/1/-2 3/1/1", "-2", -2, 1),
+            ("f 1//2 2//1 3//1", "2", 2, 1),
+            ("l 1 0", "0", 0, 4),
+            ("l 1 2 -7", "-7", -7, 4),
+        ];
+
+        for (line, token, index, count) in cases {
+            match read_after_elements(line) {
+                Err(ObjParseError::IndexOutOfRange {
+                    at,
+                    index: i,
+                    count: c,
+                }) => {
+                    assert_eq!((at.line, at.token.as_str()), (8, token), "{line}");
+                    assert_eq!((i, c), (index, count), "{line}");
+                }
+                r => panic!("{line}: {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let cases = [
+            (
+                "v 0 0 0\nv 1 0 0\n\nv 1 1 zero\n",
+                "Missing or malformed float value (line 4, 'v' directive, token 'zero')",
+            ),
+            (
+                "v 0 0 0\nv 1 0 0\n# a comment\nf 1 2 x\n",
+                "Missing or malformed index (line 4, 'f' directive, token 'x')",
+            ),
+            (
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 5\n",
+                "Index 5 is out of range, only 3 elements are defined \
+                 (line 4, 'f' directive, token '5')",
+            ),
+        ];
+
+        for (obj, message) in cases {
+            let e = read(obj.as_bytes().lines()).unwrap_err();
+            assert_eq!(e.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn objects_groups_and_smoothing() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                   o first\ng a b\ns 1\nf 1 2 3\n\
+                   g\ns off\nf 1 2 3\n\
+                   o second\ng c\ng d\ns 2\nf 1 2 3\nf 1 2 3\n";
+        let o = read(obj.as_bytes().lines()).unwrap();
+        let range = |name: &str, faces| FaceRange {
+            name: name.to_owned(),
+            faces,
+        };
+
+        assert_eq!(o.objects, [range("first", 0..2), range("second", 2..4)]);
+        // `o` leaves the groups open, and `c` is dropped for having no faces
+        assert_eq!(
+            o.groups,
+            [
+                range("a", 0..1),
+                range("b", 0..1),
+                range("default", 1..2),
+                range("d", 2..4),
+            ]
+        );
+        assert_eq!(o.smoothing_groups, [1, 0, 2, 2]);
+        assert_eq!(o.object("second").next(), Some(2..4));
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use super::mesh::Sphere;
+use crate::math::{Mat4x4, Vec4f};
+
+/// Which triangles to throw away by their winding as seen on screen, with `y` pointing up.
+///
+/// Counter-clockwise faces are the front of OBJ meshes, so [`CullMode::Clockwise`] culls back faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CullMode {
+    None,
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
+impl CullMode {
+    /// Whether a triangle with these clip-space corners gets culled.
+    ///
+    /// The sign of the determinant of the corners' `x`, `y` and `w` is the winding they
+    /// have after projection, without dividing by `w`, so it also works for triangles
+    /// that cross the camera plane.
+    pub fn culls(self, [a, b, c]: [Vec4f; 3]) -> bool {
+        let det = a.x() * (b.y() * c.w() - b.w() * c.y()) - a.y() * (b.x() * c.w() - b.w() * c.x())
+            + a.w() * (b.x() * c.y() - b.y() * c.x());
+
+        match self {
+            CullMode::None => false,
+            CullMode::Clockwise => det < 0.,
+            CullMode::CounterClockwise => det > 0.,
+        }
+    }
+}
+
+/// Triangle counts, to be reset every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Triangles handed to the renderer.
+    pub submitted: usize,
+    /// Triangles dropped by frustum or back-face culling, or clipped away entirely.
+    pub culled: usize,
+    /// Triangles that crossed a clip plane and were cut.
+    pub clipped: usize,
+    /// Triangles sent to the rasterizer, including the pieces of clipped ones.
+    pub rasterized: usize,
+}
+
+/// Settings and counters shared by the draw calls of a frame.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub cull: CullMode,
+    /// Threads [`super::tiled`] rasterizes on, 1 to stay on the calling thread.
+    pub threads: usize,
+    pub stats: Stats,
+
+    /// Started on first use and kept for later draw calls, shared by clones.
+    workers: Option<Arc<ThreadPool>>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new(CullMode::default())
+    }
+}
+
+impl Pipeline {
+    pub fn new(cull: CullMode) -> Self {
+        Self {
+            cull,
+            threads: 1,
+            stats: Stats::default(),
+            workers: None,
+        }
+    }
+
+    /// A pool of `threads` workers, started again if `threads` changed since the last call.
+    /// `None` for a single thread, or if the workers couldn't be started.
+    pub(super) fn workers(&mut self) -> Option<&ThreadPool> {
+        let n = self.threads;
+        if n <= 1 {
+            self.workers = None;
+        } else if self.workers.as_ref().map(|w| w.current_num_threads()) != Some(n) {
+            self.workers = ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build()
+                .ok()
+                .map(Arc::new);
+        }
+        self.workers.as_deref()
+    }
+
+    /// Frustum-culls a whole mesh by its bounding sphere. If it is outside, its `triangles`
+    /// count as submitted and culled, otherwise drawing them counts them.
+    pub fn mesh_visible(&mut self, mvp: Mat4x4, bounds: &Sphere, triangles: usize) -> bool {
+        let visible = sphere_in_frustum(mvp, bounds);
+        if !visible {
+            self.stats.submitted += triangles;
+            self.stats.culled += triangles;
+        }
+        visible
+    }
+}
+
+/// Whether an object-space sphere is at least partly inside the view frustum of `mvp`.
+pub fn sphere_in_frustum(mvp: Mat4x4, s: &Sphere) -> bool {
+    let m = mvp.transpose();
+    let center = Vec4f::new([s.center.x(), s.center.y(), s.center.z(), 1.]);
+
+    // each clip plane pulled back into object space
+    [
+        Vec4f::new([1., 0., 0., 1.]),
+        Vec4f::new([-1., 0., 0., 1.]),
+        Vec4f::new([0., 1., 0., 1.]),
+        Vec4f::new([0., -1., 0., 1.]),
+        Vec4f::new([0., 0., 1., 1.]),
+        Vec4f::new([0., 0., -1., 1.]),
+    ]
+    .into_iter()
+    .all(|plane| {
+        let p = m * plane;
+        let normal_len = (p.x() * p.x() + p.y() * p.y() + p.z() * p.z()).sqrt();
+        p.dot(center) >= -s.radius * normal_len
+    })
+}
@@ -0,0 +1,268 @@
+use super::{transform, Fragment, Shader};
+use crate::{
+    math::{Mat4x4, Vec3f, Vec4f, Vecf},
+    renderer::{
+        light::{self, Light, Surface},
+        mtl::{Material, NormalSpace},
+        obj::{FaceInd, ObjData},
+        ImgColor, Sampler, ShadowMap, Texture,
+    },
+};
+
+/// Where the normals in a normal map point.
+#[derive(Debug, Clone, Copy)]
+pub enum NormalMap<'a> {
+    /// Relative to the surface, blue along the normal, red towards growing `u` and green
+    /// towards growing `v`.
+    Tangent(&'a Texture),
+    /// In the model's own coordinates.
+    Object(&'a Texture),
+}
+
+impl<'a> NormalMap<'a> {
+    pub fn new(map: &'a Texture, space: NormalSpace) -> Self {
+        match space {
+            NormalSpace::Tangent => NormalMap::Tangent(map),
+            NormalSpace::Object => NormalMap::Object(map),
+        }
+    }
+}
+
+/// Per-pixel ambient, diffuse and Blinn-Phong specular lighting from a list of lights,
+/// with an MTL material's colors, shininess and texture maps.
+///
+/// Lighting happens in world space, so `lights` and `eye` are in world coordinates.
+#[derive(Debug, Clone)]
+pub struct BlinnPhongShader<'a> {
+    pub model: Mat4x4,
+    pub view_projection: Mat4x4,
+    /// Inverse transpose of `model`, for transforming normals.
+    pub normal_matrix: Mat4x4,
+
+    pub eye: Vec3f,
+    pub lights: &'a [Light],
+    /// Light reaching everything, multiplied with the material's `Ka`.
+    pub ambient_light: Vec3f,
+    /// Shadows cast from the light it was rendered for.
+    pub shadow: Option<&'a ShadowMap>,
+
+    pub material: &'a Material,
+    /// The loaded `map_Kd` texture, if the material has one.
+    pub diffuse_map: Option<&'a Texture>,
+    /// The loaded `map_Ks` texture, scaling the material's specular color per pixel.
+    pub specular_map: Option<&'a Texture>,
+    /// The loaded `map_Ke` texture, scaling the material's emissive color per pixel.
+    pub emissive_map: Option<&'a Texture>,
+    pub normal_map: Option<NormalMap<'a>>,
+    /// How all the maps are read.
+    pub sampler: Sampler,
+}
+
+impl<'a> BlinnPhongShader<'a> {
+    pub fn new(
+        model: Mat4x4,
+        view_projection: Mat4x4,
+        eye: Vec3f,
+        lights: &'a [Light],
+        material: &'a Material,
+    ) -> Self {
+        Self {
+            model,
+            view_projection,
+            normal_matrix: super::normal_matrix(model),
+
+            eye,
+            lights,
+            ambient_light: Vec3f::one(),
+            shadow: None,
+
+            material,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            normal_map: None,
+            sampler: Sampler::default(),
+        }
+    }
+}
+
+impl<C: ImgColor> Shader<C> for BlinnPhongShader<'_> {
+    /// World position, normal, `u` and `v`, then tangent and bitangent.
+    type Varying = Vecf<14>;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        let world = self.model * o.vertex(face[nth]);
+        let n = o
+            .vertex_normal(face[nth])
+            .unwrap_or_else(|| o.face_normal(face));
+        let n = transform(self.normal_matrix, n).normalized();
+        let p = world.div_w();
+        let uv = o.texture_coordinate(face[nth]).unwrap_or(Vec3f::zero());
+
+        // the same for every corner, smoothed by orthogonalizing against the normal per pixel
+        let (t, b) = match self.normal_map {
+            Some(NormalMap::Tangent(_)) => o
+                .face_tangents(face)
+                .map(|(t, b)| (transform(self.model, t), transform(self.model, b)))
+                .unwrap_or((Vec3f::zero(), Vec3f::zero())),
+            _ => (Vec3f::zero(), Vec3f::zero()),
+        };
+
+        (
+            self.view_projection * world,
+            Vecf::new([
+                p.x(),
+                p.y(),
+                p.z(),
+                n.x(),
+                n.y(),
+                n.z(),
+                uv.u(),
+                uv.v(),
+                t.x(),
+                t.y(),
+                t.z(),
+                b.x(),
+                b.y(),
+                b.z(),
+            ]),
+        )
+    }
+
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C> {
+        let v = frag.varying;
+        let pos = Vec3f::new([v[0], v[1], v[2]]);
+        let normal = Vec3f::new([v[3], v[4], v[5]]).normalized();
+        let uv = |v: Vecf<14>| Vec3f::new([v[6], v[7], 0.]);
+        let sample = |map| {
+            self.sampler
+                .sample_grad(map, uv(v), uv(frag.ddx), uv(frag.ddy))
+        };
+        let normal = match self.normal_map {
+            Some(NormalMap::Tangent(map)) => {
+                let (t, b) = (
+                    Vec3f::new([v[8], v[9], v[10]]),
+                    Vec3f::new([v[11], v[12], v[13]]),
+                );
+                // Gram-Schmidt, keeping the frame's handedness for mirrored UVs
+                let t = t - normal * normal.dot(t);
+                let b = b - normal * normal.dot(b) - t * (t.dot(b) / t.len_sqr().max(f64::EPSILON));
+                if t.len_sqr() > 0. && b.len_sqr() > 0. {
+                    let m = unpack(sample(map));
+                    (t.normalized() * m.x() + b.normalized() * m.y() + normal * m.z()).normalized()
+                } else {
+                    normal
+                }
+            }
+            Some(NormalMap::Object(map)) => {
+                transform(self.normal_matrix, unpack(sample(map))).normalized()
+            }
+            None => normal,
+        };
+
+        let m = self.material;
+        let (diffuse, a) = modulate(m.diffuse, self.diffuse_map.map(sample));
+        let (specular, _) = modulate(m.specular, self.specular_map.map(sample));
+        let (emissive, _) = modulate(m.emissive, self.emissive_map.map(sample));
+        let alpha = m.dissolve * a;
+
+        let surface = Surface {
+            pos,
+            normal,
+            ambient: m.ambient,
+            diffuse,
+            specular,
+            shininess: m.shininess,
+        };
+        let visibility = |i| match self.shadow {
+            Some(map) if map.light == i => map.visibility(pos),
+            _ => 1.,
+        };
+        let c = emissive
+            + light::blinn_phong(
+                self.lights,
+                self.ambient_light,
+                &surface,
+                self.eye,
+                visibility,
+            );
+
+        Some((c.x().min(1.), c.y().min(1.), c.z().min(1.), alpha).into())
+    }
+
+    fn needs_derivatives(&self) -> bool {
+        let maps = [self.diffuse_map, self.specular_map, self.emissive_map];
+        (maps.iter().any(Option::is_some) || self.normal_map.is_some())
+            && self.sampler.mipmap.is_some()
+    }
+}
+
+/// `color` times a texel read from a map, and the texel's alpha.
+fn modulate(color: Vec3f, texel: Option<(f64, f64, f64, f64)>) -> (Vec3f, f64) {
+    let Some((r, g, b, a)) = texel else {
+        return (color, 1.);
+    };
+    (Vec3f::new([color.x() * r, color.y() * g, color.z() * b]), a)
+}
+
+/// A normal stored as a color, from `[0, 1]` back to `[-1, 1]`.
+fn unpack((r, g, b, _): (f64, f64, f64, f64)) -> Vec3f {
+    Vec3f::new([r, g, b]) * 2. - Vec3f::one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::Vec2i,
+        renderer::{mtl, obj, tiled, CullMode, Framebuffer, Pipeline},
+        MyColor,
+    };
+
+    #[test]
+    fn object_space_normal_map() {
+        use std::io::BufRead;
+
+        // a square facing the camera, `u` running up and `v` to the left
+        let square = "v -0.5 -0.5 0\nv 0.5 -0.5 0\nv 0.5 0.5 0\nv -0.5 0.5 0\n\
+                      vt 0 1\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\n\
+                      f 1/1/1 2/2/1 3/3/1 4/4/1\n";
+        let o = obj::read(square.as_bytes().lines()).unwrap();
+        let materials = mtl::read("newmtl m\nnorm -type object m_nm.tga\n".as_bytes().lines());
+        let material = &materials.unwrap()[0];
+        assert_eq!(material.normal_space, NormalSpace::Object);
+
+        // every normal along the model's x axis
+        let map = Texture::from_fn(2, 2, |_, _| image::Rgba([1., 0.5, 0.5, 1.]));
+        let model = Mat4x4::rotation_z(0.5);
+        let normal = Vec3f::new([0.5f64.cos(), 0.5f64.sin(), 0.]);
+        // lighting only what faces that way
+        let lights = [Light::directional(-normal, Vec3f::one(), 1.)];
+
+        let render = |normal_map| {
+            let shader = BlinnPhongShader {
+                normal_map: Some(normal_map),
+                ..BlinnPhongShader::new(
+                    model,
+                    Mat4x4::identity(),
+                    Vec3f::new([0., 0., 2.]),
+                    &lights,
+                    material,
+                )
+            };
+            let mut fb = Framebuffer::new(32, 32, MyColor::from((0.5, 0.5, 0.5)));
+            tiled::draw(&mut fb, &o, &shader, &mut Pipeline::new(CullMode::None));
+            fb.get_px(Vec2i::new([16, 16])).cloned()
+        };
+
+        assert_eq!(
+            render(NormalMap::new(&map, material.normal_space)),
+            Some(MyColor::from((1., 1., 1.)))
+        );
+        // read as a tangent-space map it points along `u`, away from the light
+        assert_eq!(
+            render(NormalMap::Tangent(&map)),
+            Some(MyColor::from((0., 0., 0.)))
+        );
+    }
+}
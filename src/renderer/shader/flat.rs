@@ -0,0 +1,35 @@
+use super::{transform, Fragment, Shader};
+use crate::{
+    math::{Mat4x4, Vec3f, Vec4f},
+    renderer::{
+        obj::{FaceInd, ObjData},
+        ImgColor,
+    },
+};
+
+/// One color per face, scaled by how directly the face is lit.
+#[derive(Debug, Clone)]
+pub struct FlatShader<C> {
+    pub mvp: Mat4x4,
+    /// [`super::normal_matrix`] of the model matrix, for transforming normals.
+    pub normal_matrix: Mat4x4,
+    pub light_dir: Vec3f,
+    pub color: C,
+}
+
+impl<C: ImgColor> Shader<C> for FlatShader<C> {
+    type Varying = f64;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        let normal = transform(self.normal_matrix, o.face_normal(face)).normalized();
+        let intensity = normal.dot(-self.light_dir);
+        (self.mvp * o.vertex(face[nth]), intensity)
+    }
+
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C> {
+        let int = frag.varying.max(0.);
+
+        let (r, g, b, a): (f64, f64, f64, f64) = self.color.clone().into();
+        Some((int * r, int * g, int * b, a).into())
+    }
+}
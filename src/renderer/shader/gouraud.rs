@@ -0,0 +1,57 @@
+use super::{transform, Fragment, Shader};
+use crate::{
+    math::{Mat4x4, Vec3f, Vec4f},
+    renderer::{
+        obj::{FaceInd, ObjData},
+        ImgColor, Sampler, Texture,
+    },
+};
+
+/// Lights each vertex by its normal and interpolates the intensity across the face.
+#[derive(Debug, Clone)]
+pub struct GouraudShader<'a, C> {
+    pub mvp: Mat4x4,
+    /// [`super::normal_matrix`] of the model matrix, for transforming normals.
+    pub normal_matrix: Mat4x4,
+    pub light_dir: Vec3f,
+    pub color: C,
+    /// Multiplied with `color` if given.
+    pub texture: Option<&'a Texture>,
+    pub sampler: Sampler,
+}
+
+impl<C: ImgColor> Shader<C> for GouraudShader<'_, C> {
+    /// `u`, `v` and the light intensity.
+    type Varying = Vec3f;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        let normal = o
+            .vertex_normal(face[nth])
+            .unwrap_or_else(|| o.face_normal(face));
+        let normal = transform(self.normal_matrix, normal).normalized();
+        let uv = o.texture_coordinate(face[nth]).unwrap_or(Vec3f::zero());
+
+        (
+            self.mvp * o.vertex(face[nth]),
+            Vec3f::new([uv.u(), uv.v(), normal.dot(-self.light_dir)]),
+        )
+    }
+
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C> {
+        let int = frag.varying.z().max(0.);
+
+        let (mut r, mut g, mut b, mut a): (f64, f64, f64, f64) = self.color.clone().into();
+        if let Some(texture) = self.texture {
+            let (tr, tg, tb, ta) =
+                self.sampler
+                    .sample_grad(texture, frag.varying, frag.ddx, frag.ddy);
+            (r, g, b, a) = (r * tr, g * tg, b * tb, a * ta);
+        }
+
+        Some((int * r, int * g, int * b, a).into())
+    }
+
+    fn needs_derivatives(&self) -> bool {
+        self.texture.is_some() && self.sampler.mipmap.is_some()
+    }
+}
@@ -0,0 +1,67 @@
+use std::ops::{Add, Mul};
+
+use super::obj::{FaceInd, ObjData};
+use crate::math::{Mat4x4, Vec2i, Vec3f, Vec4f};
+
+pub mod blinn_phong;
+pub mod flat;
+pub mod gouraud;
+pub mod phong;
+pub mod textured;
+
+pub use blinn_phong::{BlinnPhongShader, NormalMap};
+pub use flat::FlatShader;
+pub use gouraud::GouraudShader;
+pub use phong::PhongShader;
+pub use textured::TexturedShader;
+
+/// Inverse transpose of `model`, for transforming normals.
+///
+/// It takes normals into the space `model` takes positions to, where the lights are, while
+/// keeping them perpendicular to the surface under non-uniform scaling. The lights of the
+/// basic shaders are in the model's own coordinates when this is the identity.
+pub fn normal_matrix(model: Mat4x4) -> Mat4x4 {
+    model.inverse().unwrap_or(model).transpose()
+}
+
+/// `m`'s upper left 3x3 applied to `v`, for directions.
+fn transform(m: Mat4x4, v: Vec3f) -> Vec3f {
+    let v = m * Vec4f::new([v.x(), v.y(), v.z(), 0.]);
+    Vec3f::new([v.x(), v.y(), v.z()])
+}
+
+/// Per-vertex shader output that gets interpolated across the triangle.
+///
+/// Implemented for anything that can be weighted and summed, like `f64` and `Vecf<N>`.
+pub trait Varying: Copy + Add<Output = Self> + Mul<f64, Output = Self> {}
+impl<T: Copy + Add<Output = T> + Mul<f64, Output = T>> Varying for T {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment<V> {
+    /// Pixel being shaded.
+    pub pos: Vec2i,
+    /// Depth in `[0, 1]`, 0 at the near plane, or 1 with [`crate::renderer::Camera::reversed_z`].
+    pub depth: f64,
+    pub varying: V,
+    /// How much `varying` changes to the next pixel to the right, e.g. for picking mipmaps.
+    /// Zero unless [`Shader::needs_derivatives`].
+    pub ddx: V,
+    /// How much `varying` changes to the next pixel down, like `ddx`.
+    pub ddy: V,
+}
+
+pub trait Shader<C> {
+    type Varying: Varying;
+
+    /// Clip-space position and varyings of corner `nth` of `face`.
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying);
+
+    /// Color of a covered pixel, or `None` to discard it.
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C>;
+
+    /// Whether `fragment` reads [`Fragment::ddx`] and [`Fragment::ddy`], which take
+    /// interpolating the varyings twice more per pixel.
+    fn needs_derivatives(&self) -> bool {
+        false
+    }
+}
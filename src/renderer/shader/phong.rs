@@ -0,0 +1,60 @@
+use super::{transform, Fragment, Shader};
+use crate::{
+    math::{Mat4x4, Vec3f, Vec4f, Vecf},
+    renderer::{
+        obj::{FaceInd, ObjData},
+        ImgColor, Sampler, Texture,
+    },
+};
+
+/// Interpolates vertex normals across the face and lights every pixel by its own normal.
+#[derive(Debug, Clone)]
+pub struct PhongShader<'a, C> {
+    pub mvp: Mat4x4,
+    /// [`super::normal_matrix`] of the model matrix, for transforming normals.
+    pub normal_matrix: Mat4x4,
+    pub light_dir: Vec3f,
+    pub color: C,
+    /// Multiplied with `color` if given.
+    pub texture: Option<&'a Texture>,
+    pub sampler: Sampler,
+}
+
+impl<C: ImgColor> Shader<C> for PhongShader<'_, C> {
+    /// Normal, then `u` and `v`.
+    type Varying = Vecf<5>;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        let n = o
+            .vertex_normal(face[nth])
+            .unwrap_or_else(|| o.face_normal(face));
+        let n = transform(self.normal_matrix, n).normalized();
+        let uv = o.texture_coordinate(face[nth]).unwrap_or(Vec3f::zero());
+
+        (
+            self.mvp * o.vertex(face[nth]),
+            Vecf::new([n.x(), n.y(), n.z(), uv.u(), uv.v()]),
+        )
+    }
+
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C> {
+        let v = frag.varying;
+        let normal = Vec3f::new([v[0], v[1], v[2]]).normalized();
+        let int = normal.dot(-self.light_dir).max(0.);
+
+        let (mut r, mut g, mut b, mut a): (f64, f64, f64, f64) = self.color.clone().into();
+        if let Some(texture) = self.texture {
+            let uv = |v: Vecf<5>| Vec3f::new([v[3], v[4], 0.]);
+            let (tr, tg, tb, ta) =
+                self.sampler
+                    .sample_grad(texture, uv(v), uv(frag.ddx), uv(frag.ddy));
+            (r, g, b, a) = (r * tr, g * tg, b * tb, a * ta);
+        }
+
+        Some((int * r, int * g, int * b, a).into())
+    }
+
+    fn needs_derivatives(&self) -> bool {
+        self.texture.is_some() && self.sampler.mipmap.is_some()
+    }
+}
@@ -0,0 +1,48 @@
+use super::{transform, Fragment, Shader};
+use crate::{
+    math::{Mat4x4, Vec3f, Vec4f},
+    renderer::{
+        obj::{FaceInd, ObjData},
+        ImgColor, Sampler, Texture,
+    },
+};
+
+/// Diffuse texture lookup, modulated by the face's lighting like [`super::FlatShader`].
+#[derive(Debug, Clone)]
+pub struct TexturedShader<'a> {
+    pub mvp: Mat4x4,
+    /// [`super::normal_matrix`] of the model matrix, for transforming normals.
+    pub normal_matrix: Mat4x4,
+    pub light_dir: Vec3f,
+    pub texture: &'a Texture,
+    pub sampler: Sampler,
+}
+
+impl<C: ImgColor> Shader<C> for TexturedShader<'_> {
+    /// `u`, `v` and the light intensity.
+    type Varying = Vec3f;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        let normal = transform(self.normal_matrix, o.face_normal(face)).normalized();
+        let intensity = normal.dot(-self.light_dir);
+        let uv = o.texture_coordinate(face[nth]).unwrap_or(Vec3f::zero());
+
+        (
+            self.mvp * o.vertex(face[nth]),
+            Vec3f::new([uv.u(), uv.v(), intensity]),
+        )
+    }
+
+    fn fragment(&self, frag: Fragment<Self::Varying>) -> Option<C> {
+        let int = frag.varying.z().max(0.);
+
+        let (r, g, b, a) = self
+            .sampler
+            .sample_grad(self.texture, frag.varying, frag.ddx, frag.ddy);
+        Some((int * r, int * g, int * b, a).into())
+    }
+
+    fn needs_derivatives(&self) -> bool {
+        self.sampler.mipmap.is_some()
+    }
+}
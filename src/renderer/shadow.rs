@@ -0,0 +1,209 @@
+use std::convert::Infallible;
+
+use super::{
+    camera,
+    light::{Light, LightKind},
+    mesh::Sphere,
+    obj::{FaceInd, ObjData},
+    setup, DepthBuffer, Fragment, Pipeline, Shader,
+};
+use crate::math::{Mat4x4, Vec2i, Vec3f, Vec4f};
+
+/// Depth of the scene as seen from a light, for telling whether points are lit by it.
+///
+/// Fill it with [`ShadowMap::draw`] for every shadow caster before the main pass.
+#[derive(Debug, Clone)]
+pub struct ShadowMap {
+    /// World space to the light's clip space.
+    pub view_projection: Mat4x4,
+    /// Index of the light in the list handed to the shader.
+    pub light: usize,
+
+    /// Subtracted from a point's depth before comparing, so surfaces don't shadow themselves.
+    pub bias: f64,
+    /// Texels around a point to compare with in every direction, 0 for hard shadows.
+    pub pcf_radius: i32,
+
+    depth: DepthBuffer,
+}
+
+impl ShadowMap {
+    pub fn new(view_projection: Mat4x4, light: usize, resolution: usize) -> Self {
+        Self {
+            view_projection,
+            light,
+
+            bias: 0.005,
+            pcf_radius: 1,
+
+            depth: DepthBuffer::new(resolution, resolution),
+        }
+    }
+
+    /// A map for `lights[light]` covering the world-space sphere `bounds`.
+    ///
+    /// `None` for point lights, which would need one map per direction.
+    pub fn for_light(
+        lights: &[Light],
+        light: usize,
+        bounds: &Sphere,
+        resolution: usize,
+    ) -> Option<Self> {
+        let (c, r) = (bounds.center, bounds.radius.max(f64::EPSILON));
+
+        let view_projection = match lights.get(light)?.kind {
+            LightKind::Directional { direction } => {
+                let eye = c - direction * (2. * r);
+                camera::orthographic(-r, r, -r, r, r, 3. * r)
+                    * camera::look_at(eye, c, up(direction))
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                outer,
+                ..
+            } => {
+                let near = ((c - position).len() - r).max(r * 0.01);
+                let far = (c - position).len() + r;
+                let fov = (2. * outer).min(std::f64::consts::PI * 0.95);
+                camera::perspective(fov, 1., near, far)
+                    * camera::look_at(position, position + direction, up(direction))
+            }
+            LightKind::Point { .. } => return None,
+        };
+
+        Some(Self::new(view_projection, light, resolution))
+    }
+
+    pub fn clear(&mut self) {
+        self.depth.clear();
+    }
+
+    /// Renders the depth of `o`, placed in the world by `model`, into the map.
+    pub fn draw(&mut self, o: &ObjData, model: Mat4x4) {
+        let size = Vec2i::new([self.depth.width() as i32, self.depth.height() as i32]);
+        let shader = DepthPass {
+            mvp: self.view_projection * model,
+        };
+        // both sides cast shadows
+        let mut pipeline = Pipeline::new(super::CullMode::None);
+
+        let depth = &mut self.depth;
+        let max = Vec2i::new([size.width() - 1, size.height() - 1]);
+        let Ok(()) = setup::<_, (), _>(
+            o,
+            0..o.faces.len(),
+            &shader,
+            &mut pipeline,
+            camera::viewport(size),
+            |t| {
+                super::coverage(t.pos, Vec2i::zero(), max, |p, z, _| {
+                    if let Some(i) = depth.index(p).filter(|&i| depth.test(i, z)) {
+                        depth.set(i, z);
+                    }
+                    Ok::<_, Infallible>(())
+                })
+            },
+        );
+    }
+
+    /// How much of the light reaches the world-space point `pos`, from 0 in full shadow
+    /// to 1. Points outside the map are lit.
+    pub fn visibility(&self, pos: Vec3f) -> f64 {
+        let clip = self.view_projection * Vec4f::new([pos.x(), pos.y(), pos.z(), 1.]);
+        if clip.w() <= 0. {
+            return 1.;
+        }
+
+        let size = Vec2i::new([self.depth.width() as i32, self.depth.height() as i32]);
+        let p = (camera::viewport(size) * clip).div_w();
+        if p.z() > 1. {
+            return 1.;
+        }
+
+        let (x, y) = (p.x().round() as i32, p.y().round() as i32);
+        let z = p.z() - self.bias;
+
+        let (mut lit, mut total) = (0, 0);
+        for dy in -self.pcf_radius..=self.pcf_radius {
+            for dx in -self.pcf_radius..=self.pcf_radius {
+                let Some(d) = self.depth.get(Vec2i::new([x + dx, y + dy])) else {
+                    continue;
+                };
+                total += 1;
+                if z <= d {
+                    lit += 1;
+                }
+            }
+        }
+
+        if total == 0 {
+            1.
+        } else {
+            lit as f64 / total as f64
+        }
+    }
+
+    pub fn depth(&self) -> &DepthBuffer {
+        &self.depth
+    }
+}
+
+/// Some vector not parallel to `direction`.
+fn up(direction: Vec3f) -> Vec3f {
+    if direction.normalized().y().abs() > 0.99 {
+        Vec3f::new([0., 0., 1.])
+    } else {
+        Vec3f::new([0., 1., 0.])
+    }
+}
+
+/// Positions only, the depth pass has no fragments to shade.
+struct DepthPass {
+    mvp: Mat4x4,
+}
+
+impl Shader<()> for DepthPass {
+    type Varying = f64;
+
+    fn vertex(&self, o: &ObjData, face: &[FaceInd; 3], nth: usize) -> (Vec4f, Self::Varying) {
+        (self.mvp * o.vertex(face[nth]), 0.)
+    }
+
+    fn fragment(&self, _: Fragment<Self::Varying>) -> Option<()> {
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+
+    #[test]
+    fn occluder_casts_shadow() {
+        // a small square hovering above a big one, lit from straight above
+        let obj = "v -0.2 1 -0.2\nv 0.2 1 -0.2\nv 0.2 1 0.2\nv -0.2 1 0.2\n\
+                   v -1 0 -1\nv 1 0 -1\nv 1 0 1\nv -1 0 1\n\
+                   f 1 2 3 4\nf 5 6 7 8\n";
+        let o = crate::renderer::obj::read(obj.as_bytes().lines()).unwrap();
+        let lights = [Light::directional(
+            Vec3f::new([0., -1., 0.]),
+            Vec3f::one(),
+            1.,
+        )];
+        let bounds = crate::renderer::mesh::bounding_sphere(&o.vertices);
+
+        let mut map = ShadowMap::for_light(&lights, 0, &bounds, 128).unwrap();
+        map.draw(&o, Mat4x4::identity());
+
+        assert_eq!(map.visibility(Vec3f::new([0., 0., 0.])), 0.);
+        assert_eq!(map.visibility(Vec3f::new([0.7, 0., 0.7])), 1.);
+        // the occluder doesn't shadow itself
+        assert_eq!(map.visibility(Vec3f::new([0., 1., 0.])), 1.);
+        // soft at the edge of the shadow
+        let edge = map.visibility(Vec3f::new([0.2, 0., 0.]));
+        assert!(edge > 0. && edge < 1., "{edge}");
+    }
+}
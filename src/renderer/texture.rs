@@ -0,0 +1,336 @@
+use image::{Rgba, Rgba32FImage};
+
+use crate::math::Vec3f;
+
+/// An image to sample, with the mipmaps made for it if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    /// Full size first, every next one half the size of the one before, down to 1 by 1.
+    levels: Vec<Rgba32FImage>,
+}
+
+impl Texture {
+    pub fn new(image: Rgba32FImage) -> Self {
+        Self {
+            levels: vec![image],
+        }
+    }
+
+    pub fn from_fn(width: u32, height: u32, f: impl FnMut(u32, u32) -> Rgba<f32>) -> Self {
+        Self::new(Rgba32FImage::from_fn(width, height, f))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.levels[0].width()
+    }
+    pub fn height(&self) -> u32 {
+        self.levels[0].height()
+    }
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.levels[0].dimensions()
+    }
+
+    /// The full size image.
+    pub fn image(&self) -> &Rgba32FImage {
+        &self.levels[0]
+    }
+
+    /// The full size image followed by its mipmaps.
+    pub fn levels(&self) -> &[Rgba32FImage] {
+        &self.levels
+    }
+
+    /// Makes the mipmaps by averaging 2x2 blocks, or 3 texels wide or high blocks for odd
+    /// sizes, replacing any there were. Empty images get none.
+    pub fn generate_mipmaps(&mut self) {
+        self.levels.truncate(1);
+
+        loop {
+            let last = &self.levels[self.levels.len() - 1];
+            let (w, h) = last.dimensions();
+            if (w <= 1 && h <= 1) || w == 0 || h == 0 {
+                break;
+            }
+
+            let next = Rgba32FImage::from_fn((w / 2).max(1), (h / 2).max(1), |x, y| {
+                let mut sum = [0.; 4];
+                for (y, wy) in taps(y, h) {
+                    for (x, wx) in taps(x, w) {
+                        for (s, c) in sum.iter_mut().zip(last.get_pixel(x, y).0) {
+                            *s += c * wx * wy;
+                        }
+                    }
+                }
+                Rgba(sum)
+            });
+            self.levels.push(next);
+        }
+    }
+
+    pub fn with_mipmaps(mut self) -> Self {
+        self.generate_mipmaps();
+        self
+    }
+}
+
+/// Texels of a row or column `n` long averaged into texel `i` of the next mipmap, with their
+/// weights. With odd `n` every texel still counts as much, the middle one of three being
+/// split between two.
+fn taps(i: u32, n: u32) -> [(u32, f32); 3] {
+    if n == 1 {
+        return [(0, 1.), (0, 0.), (0, 0.)];
+    }
+    let x = i * 2;
+    if n.is_multiple_of(2) {
+        return [(x, 0.5), (x + 1, 0.5), (x + 1, 0.)];
+    }
+
+    let (m, n) = ((n / 2) as f32, n as f32);
+    let i = i as f32;
+    [(x, (m - i) / n), (x + 1, m / n), (x + 2, (i + 1.) / n)]
+}
+
+impl From<Rgba32FImage> for Texture {
+    fn from(image: Rgba32FImage) -> Self {
+        Self::new(image)
+    }
+}
+
+/// How texels near a sample point are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    /// The closest one.
+    #[default]
+    Nearest,
+    /// A weighted average of the closest ones, the closest two when blending mipmaps.
+    Linear,
+}
+
+/// What texture coordinates outside `[0, 1]` read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// The texture tiles.
+    Repeat,
+    /// The edge texels stretch on.
+    #[default]
+    Clamp,
+    /// The texture tiles, every other copy flipped.
+    Mirror,
+}
+
+impl Wrap {
+    /// Texel `i` of a row or column of `n` texels.
+    fn index(self, i: i64, n: u32) -> u32 {
+        let n = n as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        };
+        i as u32
+    }
+}
+
+/// How shaders read a [`Texture`], with `v` pointing up like in OBJ files.
+///
+/// The default is a nearest-neighbour lookup into the full size image, clamping coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sampler {
+    /// Within a mipmap level.
+    pub filter: Filter,
+    /// Between mipmap levels, `None` to only ever read the full size image.
+    pub mipmap: Option<Filter>,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+}
+
+impl Sampler {
+    pub fn new(filter: Filter, mipmap: Option<Filter>, wrap: Wrap) -> Self {
+        Self {
+            filter,
+            mipmap,
+            wrap_u: wrap,
+            wrap_v: wrap,
+        }
+    }
+
+    /// Bilinear within, and linear between mipmap levels.
+    pub fn trilinear(wrap: Wrap) -> Self {
+        Self::new(Filter::Linear, Some(Filter::Linear), wrap)
+    }
+
+    /// Reads the full size image.
+    pub fn sample(&self, texture: &Texture, uv: Vec3f) -> (f64, f64, f64, f64) {
+        self.sample_lod(texture, uv, 0.)
+    }
+
+    /// Reads the mipmap level fitting how much `uv` changes to the next pixel to the right,
+    /// `ddx`, and down, `ddy`.
+    pub fn sample_grad(
+        &self,
+        texture: &Texture,
+        uv: Vec3f,
+        ddx: Vec3f,
+        ddy: Vec3f,
+    ) -> (f64, f64, f64, f64) {
+        if self.mipmap.is_none() {
+            return self.sample(texture, uv);
+        }
+
+        let (w, h) = (texture.width() as f64, texture.height() as f64);
+        // texels crossed per pixel
+        let texels = |d: Vec3f| (d.u() * w).hypot(d.v() * h);
+        let lod = texels(ddx).max(texels(ddy)).log2();
+
+        self.sample_lod(texture, uv, lod)
+    }
+
+    /// Reads mipmap level `lod`, 0 being the full size image. Fractions blend between the
+    /// two closest levels with [`Filter::Linear`] mipmapping.
+    pub fn sample_lod(&self, texture: &Texture, uv: Vec3f, lod: f64) -> (f64, f64, f64, f64) {
+        let levels = texture.levels();
+        let last = (levels.len() - 1) as f64;
+        // `max` drops NaN from degenerate derivatives, reading the full size image
+        let lod = lod.max(0.).min(last);
+
+        let c = match self.mipmap {
+            None => self.sample_level(&levels[0], uv),
+            Some(Filter::Nearest) => self.sample_level(&levels[lod.round() as usize], uv),
+            Some(Filter::Linear) => {
+                let (i, t) = (lod.floor() as usize, lod.fract());
+                let a = self.sample_level(&levels[i], uv);
+                if t > 0. {
+                    let b = self.sample_level(&levels[i + 1], uv);
+                    std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t)
+                } else {
+                    a
+                }
+            }
+        };
+
+        (c[0], c[1], c[2], c[3])
+    }
+
+    fn sample_level(&self, image: &Rgba32FImage, uv: Vec3f) -> [f64; 4] {
+        let (w, h) = image.dimensions();
+        if w == 0 || h == 0 {
+            return [0.; 4];
+        }
+
+        // in texels, with their centers on whole numbers
+        let x = uv.u() * w as f64 - 0.5;
+        let y = (1. - uv.v()) * h as f64 - 0.5;
+        let texel = |x: f64, y: f64| -> [f64; 4] {
+            let (x, y) = (
+                self.wrap_u.index(x as i64, w),
+                self.wrap_v.index(y as i64, h),
+            );
+            image.get_pixel(x, y).0.map(|c| c as f64)
+        };
+
+        match self.filter {
+            Filter::Nearest => texel((x + 0.5).floor(), (y + 0.5).floor()),
+            Filter::Linear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let [a, b, c, d] = [
+                    texel(x0, y0),
+                    texel(x0 + 1., y0),
+                    texel(x0, y0 + 1.),
+                    texel(x0 + 1., y0 + 1.),
+                ];
+
+                std::array::from_fn(|i| {
+                    let top = a[i] + (b[i] - a[i]) * tx;
+                    let bottom = c[i] + (d[i] - c[i]) * tx;
+                    top + (bottom - top) * ty
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One texel per column, its red channel the column's index.
+    fn ramp(width: u32) -> Texture {
+        Texture::from_fn(width, 1, |x, _| Rgba([x as f32, 0., 0., 1.]))
+    }
+
+    fn red(sampler: Sampler, texture: &Texture, u: f64) -> f64 {
+        sampler.sample(texture, Vec3f::new([u, 0.5, 0.])).0
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let t = ramp(4);
+        // texel centers at 1/8, 3/8, ...
+        let at = |wrap, u| red(Sampler::new(Filter::Nearest, None, wrap), &t, u);
+
+        assert_eq!(at(Wrap::Clamp, 0.375), 1.);
+        assert_eq!(at(Wrap::Clamp, -0.375), 0.);
+        assert_eq!(at(Wrap::Clamp, 1.375), 3.);
+        assert_eq!(at(Wrap::Repeat, -0.375), 2.);
+        assert_eq!(at(Wrap::Repeat, 1.375), 1.);
+        assert_eq!(at(Wrap::Mirror, -0.375), 1.);
+        assert_eq!(at(Wrap::Mirror, 1.375), 2.);
+    }
+
+    #[test]
+    fn bilinear() {
+        let t = ramp(4);
+        let linear = |wrap| Sampler::new(Filter::Linear, None, wrap);
+
+        assert_eq!(red(linear(Wrap::Clamp), &t, 0.375), 1.);
+        assert_eq!(red(linear(Wrap::Clamp), &t, 0.5), 1.5);
+        assert_eq!(red(linear(Wrap::Clamp), &t, 1.), 3.);
+        // halfway between the last texel and the first one of the next copy
+        assert_eq!(red(linear(Wrap::Repeat), &t, 1.), 1.5);
+    }
+
+    #[test]
+    fn mipmaps() {
+        let t = Texture::from_fn(5, 2, |x, y| Rgba([x as f32, y as f32, 0., 1.])).with_mipmaps();
+
+        let sizes: Vec<_> = t.levels().iter().map(|l| l.dimensions()).collect();
+        assert_eq!(sizes, [(5, 2), (2, 1), (1, 1)]);
+        // the middle column is split between both texels, none is left out
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        let red = |level: usize, x| t.levels()[level].get_pixel(x, 0).0[0] as f64;
+        assert!(close(red(1, 0), 0.8) && close(red(1, 1), 3.2));
+        assert!(close(red(2, 0), 2.));
+        assert_eq!(t.levels()[1].get_pixel(1, 0).0[1], 0.5);
+
+        let s = Sampler::trilinear(Wrap::Clamp);
+        let uv = Vec3f::new([0.1, 0.5, 0.]);
+        let (a, b) = (s.sample_lod(&t, uv, 1.).0, s.sample_lod(&t, uv, 2.).0);
+        assert!(close(a, 0.8));
+        assert!(close(s.sample_lod(&t, uv, 1.5).0, (a + b) / 2.));
+        assert_eq!(s.sample_lod(&t, uv, f64::NAN), s.sample_lod(&t, uv, 0.));
+        // two texels of the full size image per pixel is one of the first mipmap
+        let ddx = Vec3f::new([2. / 5., 0., 0.]);
+        assert_eq!(s.sample_grad(&t, uv, ddx, Vec3f::zero()).0, a);
+    }
+
+    #[test]
+    fn empty_mipmaps() {
+        for (w, h) in [(0, 4), (4, 0), (0, 0)] {
+            let t = Texture::from_fn(w, h, |_, _| Rgba([1.; 4])).with_mipmaps();
+            assert_eq!(t.levels().len(), 1);
+            let uv = Vec3f::new([0.5, 0.5, 0.]);
+            assert_eq!(
+                Sampler::trilinear(Wrap::Repeat).sample_lod(&t, uv, 3.).0,
+                0.
+            );
+        }
+    }
+}
@@ -1,6 +1,37 @@
-use std::io::Read;
+use std::io::{Read, Write};
+use thiserror::Error as ThisError;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, ThisError)]
+pub enum TgaError {
+    #[error("Invalid image type {0}")]
+    BadImageType(u8),
+
+    #[error("Unsupported pixel depth {0} for {1:?}")]
+    BadPixelDepth(u8, ImageType),
+
+    #[error("Unsupported color map entry size {0}")]
+    BadColorMapEntrySize(u8),
+
+    #[error("Color mapped image without a color map")]
+    MissingColorMap,
+
+    #[error("Color map index {0} out of range")]
+    BadColorMapIndex(usize),
+
+    #[error("{0}x{1} is too large for a TGA image")]
+    TooLarge(usize, usize),
+
+    #[error("Image ID is {0} bytes long, at most 255 fit")]
+    ImageIdTooLong(usize),
+
+    #[error("Pixel buffer has {0} bytes instead of {1}")]
+    BadBufferSize(usize, usize),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorMapType {
     NoColorMap,
     Present,
@@ -8,7 +39,7 @@ pub enum ColorMapType {
     DeveloperUse,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageType {
     NoImageData = 0b0000,
     UncompressedColorMapped = 0b0001,
@@ -19,6 +50,18 @@ pub enum ImageType {
     RunLengthBlackAndWhite = 0b1011,
 }
 
+impl ImageType {
+    pub fn is_run_length(self) -> bool {
+        self as u8 & 0b1000 != 0
+    }
+    pub fn is_color_mapped(self) -> bool {
+        self as u8 & 0b0111 == 0b0001
+    }
+    pub fn is_black_and_white(self) -> bool {
+        self as u8 & 0b0111 == 0b0011
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct ImgSpec {
     x_origin: u16,
@@ -37,12 +80,252 @@ pub struct TgaImage {
     image_type: ImageType,
     img_spec: ImgSpec,
 
+    color_map_first_index: u16,
+    color_map_entry_size: u8,
+
+    /// Uncompressed pixels in file order, `pixel_depth` bits each.
     image_data: Vec<u8>,
     color_map: Vec<u8>,
     image_id: Vec<u8>,
 }
 
-pub fn read(r: &mut impl Read) -> std::io::Result<TgaImage> {
+impl TgaImage {
+    pub fn width(&self) -> usize {
+        self.img_spec.width as usize
+    }
+    pub fn height(&self) -> usize {
+        self.img_spec.height as usize
+    }
+    pub fn origin(&self) -> (u16, u16) {
+        (self.img_spec.x_origin, self.img_spec.y_origin)
+    }
+
+    pub fn color_map_type(&self) -> ColorMapType {
+        self.color_map_type
+    }
+    pub fn image_type(&self) -> ImageType {
+        self.image_type
+    }
+    pub fn image_id(&self) -> &[u8] {
+        &self.image_id
+    }
+
+    /// 32-bit true color image from 8-bit RGBA pixels, rows top to bottom.
+    pub fn from_rgba(
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+        run_length: bool,
+    ) -> Result<Self, TgaError> {
+        let (Ok(w), Ok(h)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(TgaError::TooLarge(width, height));
+        };
+        if rgba.len() != width * height * 4 {
+            return Err(TgaError::BadBufferSize(rgba.len(), width * height * 4));
+        }
+
+        let image_data = rgba
+            .chunks_exact(4)
+            .flat_map(|px| [px[2], px[1], px[0], px[3]])
+            .collect();
+
+        Ok(Self {
+            color_map_type: ColorMapType::NoColorMap,
+            image_type: if run_length {
+                ImageType::RunLengthTrueColor
+            } else {
+                ImageType::UncompressedTrueColor
+            },
+
+            img_spec: ImgSpec {
+                x_origin: 0,
+                y_origin: 0,
+                width: w,
+                height: h,
+                pixel_depth: 32,
+                alpha_depth: 8,
+                pixels_right_to_left: false,
+                pixels_top_to_bottom: true,
+            },
+
+            color_map_first_index: 0,
+            color_map_entry_size: 0,
+
+            image_data,
+            color_map: vec![],
+            image_id: vec![],
+        })
+    }
+
+    pub fn with_image_id(mut self, image_id: impl Into<Vec<u8>>) -> Result<Self, TgaError> {
+        let image_id = image_id.into();
+        if image_id.len() > u8::MAX as usize {
+            return Err(TgaError::ImageIdTooLong(image_id.len()));
+        }
+
+        self.image_id = image_id;
+        Ok(self)
+    }
+
+    /// Decodes into 8-bit RGBA, rows top to bottom and pixels left to right.
+    pub fn to_rgba(&self) -> Result<Vec<u8>, TgaError> {
+        let (w, h) = (self.width(), self.height());
+        let bpp = bytes_per_pixel(self.img_spec.pixel_depth);
+
+        let mut out = vec![0; w * h * 4];
+        if self.image_type == ImageType::NoImageData {
+            return Ok(out);
+        }
+
+        for (i, px) in self.image_data.chunks_exact(bpp).enumerate() {
+            let (x, y) = (i % w, i / w);
+            let x = if self.img_spec.pixels_right_to_left {
+                w - 1 - x
+            } else {
+                x
+            };
+            let y = if self.img_spec.pixels_top_to_bottom {
+                y
+            } else {
+                h - 1 - y
+            };
+
+            let o = (x + y * w) * 4;
+            out[o..o + 4].copy_from_slice(&self.decode_pixel(px)?);
+        }
+
+        Ok(out)
+    }
+
+    /// Decodes into the renderer's floating point texture format.
+    pub fn to_texture(&self) -> Result<super::Texture, TgaError> {
+        let data = self
+            .to_rgba()?
+            .into_iter()
+            .map(|c| c as f32 / 255.)
+            .collect();
+        Ok(
+            image::Rgba32FImage::from_raw(self.width() as u32, self.height() as u32, data)
+                .expect("buffer matches the image dimensions")
+                .into(),
+        )
+    }
+
+    fn decode_pixel(&self, px: &[u8]) -> Result<[u8; 4], TgaError> {
+        let alpha = self.img_spec.alpha_depth;
+
+        if self.image_type.is_color_mapped() {
+            let index = px.iter().rev().fold(0, |acc, &b| acc << 8 | b as usize);
+            let entry = index
+                .checked_sub(self.color_map_first_index as usize)
+                .ok_or(TgaError::BadColorMapIndex(index))?;
+
+            let size = bytes_per_pixel(self.color_map_entry_size);
+            let entry = self
+                .color_map
+                .get(entry * size..(entry + 1) * size)
+                .ok_or(TgaError::BadColorMapIndex(index))?;
+
+            // attribute bits of a color map entry are alpha unless the header says otherwise
+            let alpha = if alpha == 0 && self.color_map_entry_size == 32 {
+                8
+            } else {
+                alpha
+            };
+            return Ok(true_color(entry, alpha));
+        }
+
+        if self.image_type.is_black_and_white() {
+            let a = match px.get(1) {
+                Some(&a) if alpha > 0 => a,
+                _ => 0xff,
+            };
+            return Ok([px[0], px[0], px[0], a]);
+        }
+
+        Ok(true_color(px, alpha))
+    }
+}
+
+fn bytes_per_pixel(depth: u8) -> usize {
+    (depth as usize).div_ceil(8)
+}
+
+/// Little-endian BGR(A) or packed 15/16-bit ARRRRRGGGGGBBBBB.
+fn true_color(px: &[u8], alpha_depth: u8) -> [u8; 4] {
+    match px.len() {
+        2 => {
+            let v = u16::from_le_bytes([px[0], px[1]]);
+            let c = |shift: u16| {
+                let c = (v >> shift & 0x1f) as u8;
+                c << 3 | c >> 2
+            };
+
+            let a = if alpha_depth > 0 && v & 0x8000 == 0 {
+                0
+            } else {
+                0xff
+            };
+            [c(10), c(5), c(0), a]
+        }
+        3 => [px[2], px[1], px[0], 0xff],
+        _ => [
+            px[2],
+            px[1],
+            px[0],
+            if alpha_depth > 0 { px[3] } else { 0xff },
+        ],
+    }
+}
+
+fn check_depth(image_type: ImageType, depth: u8) -> Result<(), TgaError> {
+    let ok = match image_type {
+        ImageType::NoImageData => true,
+        ImageType::UncompressedColorMapped | ImageType::RunLengthColorMapped => {
+            matches!(depth, 8 | 16)
+        }
+        ImageType::UncompressedTrueColor | ImageType::RunLengthTrueColor => {
+            matches!(depth, 15 | 16 | 24 | 32)
+        }
+        ImageType::UncompressedBlackAndWhite | ImageType::RunLengthBlackAndWhite => {
+            matches!(depth, 8 | 16)
+        }
+    };
+
+    if ok {
+        Ok(())
+    } else {
+        Err(TgaError::BadPixelDepth(depth, image_type))
+    }
+}
+
+fn read_run_length(r: &mut impl Read, pixels: usize, bpp: usize) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(pixels * bpp);
+    let mut px = vec![0; bpp];
+
+    while data.len() < pixels * bpp {
+        let mut header = [0; 1];
+        r.read_exact(&mut header)?;
+
+        let count = (header[0] & 0x7f) as usize + 1;
+        let count = count.min(pixels - data.len() / bpp);
+
+        if header[0] & 0x80 != 0 {
+            r.read_exact(&mut px)?;
+            for _ in 0..count {
+                data.extend_from_slice(&px);
+            }
+        } else {
+            let start = data.len();
+            data.resize(start + count * bpp, 0);
+            r.read_exact(&mut data[start..])?;
+        }
+    }
+
+    Ok(data)
+}
+
+pub fn read(r: &mut impl Read) -> Result<TgaImage, TgaError> {
     let mut id_len = [0; 1];
     r.read_exact(&mut id_len)?;
     let id_len = u8::from_le_bytes(id_len);
@@ -91,40 +374,61 @@ pub fn read(r: &mut impl Read) -> std::io::Result<TgaImage> {
     r.read_exact(&mut image_descriptor)?;
     let image_descriptor = u8::from_le_bytes(image_descriptor);
 
+    let image_type = match image_type {
+        0 => ImageType::NoImageData,
+        1 => ImageType::UncompressedColorMapped,
+        2 => ImageType::UncompressedTrueColor,
+        3 => ImageType::UncompressedBlackAndWhite,
+        9 => ImageType::RunLengthColorMapped,
+        10 => ImageType::RunLengthTrueColor,
+        11 => ImageType::RunLengthBlackAndWhite,
+
+        t => return Err(TgaError::BadImageType(t)),
+    };
+    check_depth(image_type, pixel_depth)?;
+
+    let color_map_type = match color_map_type {
+        0 => ColorMapType::NoColorMap,
+        1 => ColorMapType::Present,
+        2..=127 => ColorMapType::Reserved,
+        128..=255 => ColorMapType::DeveloperUse,
+    };
+    if image_type.is_color_mapped() && color_map_type != ColorMapType::Present {
+        return Err(TgaError::MissingColorMap);
+    }
+    if color_map_type == ColorMapType::Present && !matches!(color_map_entry_size, 15 | 16 | 24 | 32)
+    {
+        return Err(TgaError::BadColorMapEntrySize(color_map_entry_size));
+    }
+
     let mut image_id = vec![0; id_len as usize];
     r.read_exact(&mut image_id)?;
 
-    let mut color_map = vec![0; color_map_length as usize * color_map_entry_size as usize];
-    r.read_exact(&mut color_map)?;
+    let mut color_map = vec![];
+    if color_map_type == ColorMapType::Present {
+        color_map.resize(
+            color_map_length as usize * bytes_per_pixel(color_map_entry_size),
+            0,
+        );
+        r.read_exact(&mut color_map)?;
+    }
 
-    let bytes_per_pixel = pixel_depth / 8;
-    let mut image_data = vec![0; width as usize * height as usize * bytes_per_pixel as usize];
-    r.read_exact(&mut image_data)?;
+    let pixels = width as usize * height as usize;
+    let bpp = bytes_per_pixel(pixel_depth);
 
-    Ok(TgaImage {
-        color_map_type: match color_map_type {
-            0 => ColorMapType::NoColorMap,
-            1 => ColorMapType::Present,
-            2..=127 => ColorMapType::Reserved,
-            128..=255 => ColorMapType::DeveloperUse,
-        },
+    let image_data = if image_type == ImageType::NoImageData {
+        vec![]
+    } else if image_type.is_run_length() {
+        read_run_length(r, pixels, bpp)?
+    } else {
+        let mut image_data = vec![0; pixels * bpp];
+        r.read_exact(&mut image_data)?;
+        image_data
+    };
 
-        image_type: match image_type {
-            0 => ImageType::NoImageData,
-            1 => ImageType::UncompressedColorMapped,
-            2 => ImageType::UncompressedTrueColor,
-            3 => ImageType::UncompressedBlackAndWhite,
-            9 => ImageType::RunLengthColorMapped,
-            10 => ImageType::RunLengthTrueColor,
-            11 => ImageType::RunLengthBlackAndWhite,
-
-            _ => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid image type",
-                ))
-            }
-        },
+    Ok(TgaImage {
+        color_map_type,
+        image_type,
 
         img_spec: ImgSpec {
             pixels_right_to_left: image_descriptor & 0x10 != 0,
@@ -137,8 +441,316 @@ pub fn read(r: &mut impl Read) -> std::io::Result<TgaImage> {
             height,
         },
 
+        color_map_first_index: first_entry_index,
+        color_map_entry_size,
+
         image_data,
         color_map,
         image_id,
     })
 }
+
+/// Run-length encodes `data` without letting packets cross scanlines.
+fn write_run_length(
+    w: &mut impl Write,
+    data: &[u8],
+    width: usize,
+    bpp: usize,
+) -> std::io::Result<()> {
+    if width == 0 || data.is_empty() {
+        return Ok(());
+    }
+
+    for row in data.chunks(width * bpp) {
+        let px: Vec<_> = row.chunks_exact(bpp).collect();
+
+        let mut i = 0;
+        while i < px.len() {
+            let run = px[i..]
+                .iter()
+                .take(128)
+                .take_while(|&&p| p == px[i])
+                .count();
+            if run > 1 {
+                w.write_all(&[0x80 | (run - 1) as u8])?;
+                w.write_all(px[i])?;
+                i += run;
+                continue;
+            }
+
+            let start = i;
+            while i < px.len() && i - start < 128 && (i + 1 == px.len() || px[i] != px[i + 1]) {
+                i += 1;
+            }
+
+            w.write_all(&[(i - start - 1) as u8])?;
+            for p in &px[start..i] {
+                w.write_all(p)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `img` with the TGA 2.0 footer, run-length encoding the pixels if its image type asks for it.
+pub fn write(w: &mut impl Write, img: &TgaImage) -> Result<(), TgaError> {
+    let spec = &img.img_spec;
+
+    if img.image_id.len() > u8::MAX as usize {
+        return Err(TgaError::ImageIdTooLong(img.image_id.len()));
+    }
+    let color_map_present = img.color_map_type == ColorMapType::Present;
+    let entry_size = bytes_per_pixel(img.color_map_entry_size);
+
+    w.write_all(&[img.image_id.len() as u8])?;
+    w.write_all(&[color_map_present as u8])?;
+    w.write_all(&[img.image_type as u8])?;
+
+    if color_map_present {
+        let len = img.color_map.len() / entry_size;
+        w.write_all(&img.color_map_first_index.to_le_bytes())?;
+        w.write_all(&(len as u16).to_le_bytes())?;
+        w.write_all(&[img.color_map_entry_size])?;
+    } else {
+        w.write_all(&[0; 5])?;
+    }
+
+    w.write_all(&spec.x_origin.to_le_bytes())?;
+    w.write_all(&spec.y_origin.to_le_bytes())?;
+    w.write_all(&spec.width.to_le_bytes())?;
+    w.write_all(&spec.height.to_le_bytes())?;
+    w.write_all(&[spec.pixel_depth])?;
+    w.write_all(&[spec.alpha_depth & 0x0f
+        | (spec.pixels_right_to_left as u8) << 4
+        | (spec.pixels_top_to_bottom as u8) << 5])?;
+
+    w.write_all(&img.image_id)?;
+    if color_map_present {
+        w.write_all(&img.color_map)?;
+    }
+
+    if img.image_type.is_run_length() {
+        let bpp = bytes_per_pixel(spec.pixel_depth);
+        write_run_length(w, &img.image_data, spec.width as usize, bpp)?;
+    } else {
+        w.write_all(&img.image_data)?;
+    }
+
+    // no extension area or developer directory
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(b"TRUEVISION-XFILE.\0")?;
+
+    Ok(())
+}
+
+/// Writes 8-bit RGBA pixels, rows top to bottom, as a 32-bit true color image.
+pub fn write_rgba(
+    w: &mut impl Write,
+    width: usize,
+    height: usize,
+    rgba: &[u8],
+    run_length: bool,
+) -> Result<(), TgaError> {
+    write(w, &TgaImage::from_rgba(width, height, rgba, run_length)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = ((i % width) as u8, (i / width) as u8);
+                // flat bands so the run-length encoder has runs to find
+                [x / 8 * 10, y * 20, 0x80, 0xff]
+            })
+            .collect()
+    }
+
+    fn round_trip(img: &TgaImage) -> (Vec<u8>, TgaImage) {
+        let mut buf = vec![];
+        write(&mut buf, img).unwrap();
+
+        let read = read(&mut buf.as_slice()).unwrap();
+        (buf, read)
+    }
+
+    #[test]
+    fn uncompressed_round_trip() {
+        let rgba = gradient(7, 5);
+        let img = TgaImage::from_rgba(7, 5, &rgba, false).unwrap();
+
+        let (buf, read) = round_trip(&img);
+        assert_eq!(read.image_type(), ImageType::UncompressedTrueColor);
+        assert_eq!((read.width(), read.height()), (7, 5));
+        assert_eq!(read.to_rgba().unwrap(), rgba);
+        assert!(buf.ends_with(b"TRUEVISION-XFILE.\0"));
+    }
+
+    #[test]
+    fn run_length_round_trip() {
+        let rgba = gradient(200, 3);
+        let img = TgaImage::from_rgba(200, 3, &rgba, true).unwrap();
+
+        let (buf, read) = round_trip(&img);
+        assert_eq!(read.image_type(), ImageType::RunLengthTrueColor);
+        assert_eq!(read.to_rgba().unwrap(), rgba);
+        assert!(buf.len() < 200 * 3 * 4);
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        for (w, h) in [(0, 4), (4, 0)] {
+            let img = TgaImage::from_rgba(w, h, &[], true).unwrap();
+
+            let (_, read) = round_trip(&img);
+            assert_eq!((read.width(), read.height()), (w, h));
+            assert!(read.to_rgba().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn image_id_round_trip() {
+        let img = TgaImage::from_rgba(1, 1, &[1, 2, 3, 4], false)
+            .and_then(|img| img.with_image_id("spiderbot"))
+            .unwrap();
+
+        let (_, read) = round_trip(&img);
+        assert_eq!(read.image_id(), b"spiderbot");
+        assert_eq!(read.to_rgba().unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_wrong_buffer_size() {
+        assert!(matches!(
+            TgaImage::from_rgba(2, 2, &[0; 15], false),
+            Err(TgaError::BadBufferSize(15, 16))
+        ));
+    }
+
+    /// A file with the header fields that matter here, then the color map and pixels in
+    /// `payload`.
+    fn file(
+        image_type: u8,
+        color_map: Option<(u16, u16, u8)>,
+        (width, height): (u16, u16),
+        depth: u8,
+        descriptor: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let (first, len, entry_size) = color_map.unwrap_or_default();
+        let mut f = vec![0, color_map.is_some() as u8, image_type];
+        f.extend(first.to_le_bytes());
+        f.extend(len.to_le_bytes());
+        f.push(entry_size);
+        f.extend([0; 4]);
+        f.extend(width.to_le_bytes());
+        f.extend(height.to_le_bytes());
+        f.extend([depth, descriptor]);
+        f.extend(payload);
+        f
+    }
+
+    fn decode(file: &[u8]) -> Vec<u8> {
+        read(&mut &file[..]).unwrap().to_rgba().unwrap()
+    }
+
+    #[test]
+    fn decodes_pixel_formats() {
+        // descriptor 0x20 is top to bottom, its low bits the alpha depth
+        let cases = [
+            (
+                "16-bit with an alpha bit",
+                file(2, None, (2, 1), 16, 0x21, &[0x00, 0x7c, 0xe0, 0x83]),
+                vec![255, 0, 0, 0, 0, 255, 0, 255],
+            ),
+            (
+                "15-bit",
+                file(2, None, (2, 1), 15, 0x20, &[0x1f, 0x00, 0x00, 0x02]),
+                vec![0, 0, 255, 255, 0, 132, 0, 255],
+            ),
+            (
+                "24-bit",
+                file(2, None, (1, 1), 24, 0x20, &[1, 2, 3]),
+                vec![3, 2, 1, 255],
+            ),
+            (
+                "32-bit without alpha bits",
+                file(2, None, (1, 1), 32, 0x20, &[1, 2, 3, 4]),
+                vec![3, 2, 1, 255],
+            ),
+            (
+                "grayscale",
+                file(3, None, (2, 1), 8, 0x20, &[10, 200]),
+                vec![10, 10, 10, 255, 200, 200, 200, 255],
+            ),
+            (
+                "grayscale with alpha",
+                file(3, None, (1, 1), 16, 0x28, &[50, 128]),
+                vec![50, 50, 50, 128],
+            ),
+            (
+                "color map with 24-bit entries, starting at index 2",
+                file(
+                    1,
+                    Some((2, 2, 24)),
+                    (2, 1),
+                    8,
+                    0x20,
+                    &[1, 2, 3, 4, 5, 6, 3, 2],
+                ),
+                vec![6, 5, 4, 255, 3, 2, 1, 255],
+            ),
+            (
+                "color map with 16-bit entries",
+                file(1, Some((0, 1, 16)), (1, 1), 8, 0x20, &[0x00, 0x7c, 0]),
+                vec![255, 0, 0, 255],
+            ),
+            (
+                "color map with 32-bit entries, alpha even without alpha bits",
+                file(1, Some((0, 1, 32)), (1, 1), 8, 0x20, &[1, 2, 3, 4, 0]),
+                vec![3, 2, 1, 4],
+            ),
+            (
+                "run-length color mapped",
+                file(
+                    9,
+                    Some((0, 2, 24)),
+                    (3, 1),
+                    8,
+                    0x20,
+                    &[1, 2, 3, 4, 5, 6, 0x81, 1, 0x00, 0],
+                ),
+                vec![6, 5, 4, 255, 6, 5, 4, 255, 3, 2, 1, 255],
+            ),
+        ];
+
+        for (name, file, rgba) in cases {
+            assert_eq!(decode(&file), rgba, "{name}");
+        }
+    }
+
+    #[test]
+    fn decodes_orientation_and_packets_across_rows() {
+        // without 0x20 rows go bottom to top, with 0x10 pixels go right to left
+        let pixels = [1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4];
+        let gray = |v: &[u8]| -> Vec<u8> { v.iter().flat_map(|&v| [v, v, v, 255]).collect() };
+        assert_eq!(
+            decode(&file(2, None, (2, 2), 24, 0x10, &pixels)),
+            gray(&[4, 3, 2, 1])
+        );
+        assert_eq!(
+            decode(&file(2, None, (2, 2), 24, 0x00, &pixels)),
+            gray(&[3, 4, 1, 2])
+        );
+
+        // a run of five spilling into the second row, then one raw pixel
+        let f = file(11, None, (3, 2), 8, 0x20, &[0x84, 7, 0x00, 9]);
+        assert_eq!(decode(&f), gray(&[7, 7, 7, 7, 7, 9]));
+        let f = file(11, None, (3, 2), 8, 0x00, &[0x84, 7, 0x00, 9]);
+        assert_eq!(decode(&f), gray(&[7, 7, 9, 7, 7, 7]));
+    }
+}
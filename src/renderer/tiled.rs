@@ -0,0 +1,190 @@
+use std::{convert::Infallible, ops::Range};
+
+use super::{
+    camera, coverage, depth::DepthRows, obj::ObjData, setup, Framebuffer, ImgColor, Pipeline,
+    ScreenTri, Shader,
+};
+use crate::math::Vec2i;
+
+/// Width and height of the tiles the screen is split into.
+pub const TILE_SIZE: usize = 64;
+
+/// Like [`super::draw`], but see [`draw_faces`].
+pub fn draw<C, S>(fb: &mut Framebuffer<C>, o: &ObjData, shader: &S, pipeline: &mut Pipeline)
+where
+    C: ImgColor + Send + Sync,
+    S: Shader<C> + Sync,
+    S::Varying: Send + Sync,
+{
+    draw_faces(fb, o, 0..o.faces.len(), shader, pipeline)
+}
+
+/// Like [`super::draw_faces`], depth tested against `fb`'s own depth plane, with the
+/// triangles binned into tiles that get rasterized on `pipeline.threads` threads.
+///
+/// Each row of tiles is drawn straight into `fb` by one thread, a tile at a time, and each
+/// tile draws its triangles in order, so the result is the same for any number of
+/// threads, and the same as [`super::draw_faces`] would draw.
+pub fn draw_faces<C, S>(
+    fb: &mut Framebuffer<C>,
+    o: &ObjData,
+    faces: Range<usize>,
+    shader: &S,
+    pipeline: &mut Pipeline,
+) where
+    C: ImgColor + Send + Sync,
+    S: Shader<C> + Sync,
+    S::Varying: Send + Sync,
+{
+    let (width, height) = (fb.width(), fb.height());
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let viewport = camera::viewport(Vec2i::new([width as i32, height as i32]));
+    let mut tris = vec![];
+    let Ok(()) = setup(o, faces, shader, pipeline, viewport, |t| {
+        tris.push(t);
+        Ok::<_, Infallible>(())
+    });
+
+    let (cols, rows) = (width.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE));
+    let bins = bin(&tris, cols, rows);
+
+    let (color, depth) = fb.planes_mut();
+    let bands: Vec<_> = color
+        .chunks_mut(width * TILE_SIZE)
+        .zip(depth.bands_mut(TILE_SIZE))
+        .enumerate()
+        .filter(|(row, _)| bins[row * cols..][..cols].iter().any(|b| !b.is_empty()))
+        .collect();
+
+    let derivatives = shader.needs_derivatives();
+    let render = |(row, (color, mut depth)): (usize, (&mut [C], DepthRows))| {
+        let y0 = row * TILE_SIZE;
+        let y1 = (y0 + TILE_SIZE).min(height);
+
+        for col in 0..cols {
+            let x0 = col * TILE_SIZE;
+            let x1 = (x0 + TILE_SIZE).min(width);
+            let min = Vec2i::new([x0 as i32, y0 as i32]);
+            let max = Vec2i::new([x1 as i32 - 1, y1 as i32 - 1]);
+
+            for &t in &bins[row * cols + col] {
+                let t = &tris[t];
+                let Ok(()) = coverage(t.pos, min, max, |p, z, bc| {
+                    let i = p.x() as usize + (p.y() as usize - y0) * width;
+                    if !depth.test(i, z) {
+                        return Ok::<_, Infallible>(());
+                    }
+
+                    if let Some(frag) = shader.fragment(t.fragment(p, z, bc, derivatives)) {
+                        color[i] = frag;
+                        depth.set(i, z);
+                    }
+                    Ok(())
+                });
+            }
+        }
+    };
+
+    match pipeline.workers() {
+        Some(workers) if bands.len() > 1 => workers.scope(|s| {
+            let render = &render;
+            for band in bands {
+                s.spawn(move |_| render(band));
+            }
+        }),
+        _ => bands.into_iter().for_each(render),
+    }
+}
+
+/// Indices of the triangles that may touch each tile, in drawing order.
+fn bin<V>(tris: &[ScreenTri<V>], cols: usize, rows: usize) -> Vec<Vec<usize>> {
+    let mut bins = vec![vec![]; cols * rows];
+
+    for (i, t) in tris.iter().enumerate() {
+        // a pixel of slack around the bounding box, `coverage` stays inside the tile anyway
+        let tile = |c: f64, n: usize| ((c.max(0.) as usize) / TILE_SIZE).min(n - 1);
+        let lo = |a: usize| t.pos.iter().map(|p| p[a]).fold(f64::INFINITY, f64::min) - 1.;
+        let hi = |a: usize| t.pos.iter().map(|p| p[a]).fold(f64::NEG_INFINITY, f64::max) + 1.;
+
+        if hi(0) < 0. || hi(1) < 0. {
+            continue;
+        }
+        for ty in tile(lo(1), rows)..=tile(hi(1), rows) {
+            for tx in tile(lo(0), cols)..=tile(hi(0), cols) {
+                bins[ty * cols + tx].push(i);
+            }
+        }
+    }
+
+    bins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{Mat4x4, Vec3f},
+        renderer::{shader, Camera, CullMode, DepthBuffer, Projection, TexturedShader},
+        MyColor,
+    };
+
+    #[test]
+    fn same_as_single_threaded() {
+        use std::io::BufRead;
+
+        let file = std::fs::File::open("african_head.obj").unwrap();
+        let o = crate::renderer::obj::read(std::io::BufReader::new(file).lines()).unwrap();
+        let texture = crate::renderer::Texture::from_fn(16, 16, |x, y| {
+            image::Rgba([x as f32 / 16., y as f32 / 16., 0.5, 1.])
+        });
+
+        let camera = Camera::new(
+            Vec3f::new([0.3, 0.2, 2.]),
+            Vec3f::zero(),
+            Vec3f::new([0., 1., 0.]),
+            Projection::Perspective {
+                fov_y: 1.,
+                near: 0.1,
+                far: 10.,
+            },
+        );
+        // odd sizes, so the last row and column of tiles are partial
+        let (w, h) = (203, 150);
+        let shader = TexturedShader {
+            mvp: camera.view_projection(w as f64 / h as f64) * Mat4x4::rotation_y(0.7),
+            normal_matrix: shader::normal_matrix(Mat4x4::rotation_y(0.7)),
+            light_dir: Vec3f::new([0., 0., -1.]),
+            texture: &texture,
+            sampler: Default::default(),
+        };
+        let background = MyColor::from((0., 0., 0.));
+
+        for cull in [CullMode::Clockwise, CullMode::None] {
+            let mut expected = Framebuffer::new(w, h, background.clone());
+            let mut depth = DepthBuffer::new(w, h);
+            let mut pipeline = Pipeline::new(cull);
+            crate::renderer::draw(&mut expected, &o, &shader, &mut pipeline, Some(&mut depth))
+                .unwrap();
+            let stats = pipeline.stats;
+            assert!(expected.pixels().iter().any(|c| *c != background));
+
+            for threads in [1, 2, 5] {
+                let mut fb = Framebuffer::new(w, h, background.clone());
+                let mut pipeline = Pipeline::new(cull);
+                pipeline.threads = threads;
+                // twice, the second time with the pool started by the first
+                draw(&mut fb, &o, &shader, &mut pipeline);
+                pipeline.stats = Default::default();
+                fb.clear(background.clone());
+                draw(&mut fb, &o, &shader, &mut pipeline);
+
+                assert!(fb.pixels() == expected.pixels(), "{threads} threads");
+                assert_eq!(fb.depth(), &depth, "{threads} threads");
+                assert_eq!(pipeline.stats, stats);
+            }
+        }
+    }
+}